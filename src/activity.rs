@@ -0,0 +1,89 @@
+use crate::fs_utils::write_atomic;
+use crate::schema::{load_versioned, Migration};
+use crate::time_utils::unix_epoch_to_datetime;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Weeks of a busy watch, the oldest events being dropped first
+const MAX_EVENTS: usize = 2000;
+
+const MIGRATIONS: &[Migration] = &[];
+
+/// Something the CLI noticed while running, e.g. a new story found by `watch`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActivityEvent {
+    /// When it was noticed, as a unix timestamp
+    pub timestamp: u64,
+    pub message: String,
+    /// The item it is about
+    pub item: Option<i64>,
+}
+
+impl std::fmt::Display for ActivityEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            unix_epoch_to_datetime(self.timestamp),
+            self.message
+        )?;
+        if let Some(item) = self.item {
+            write!(f, " (hn {})", item)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ActivityFile {
+    version: u64,
+    #[serde(default)]
+    events: Vec<ActivityEvent>,
+}
+
+/// The events noticed across runs, oldest first
+pub struct ActivityLog {
+    path: PathBuf,
+    events: Vec<ActivityEvent>,
+    corrupt_backup: Option<PathBuf>,
+}
+
+impl ActivityLog {
+    /// Starts afresh when the file is missing or corrupted, the latter being moved aside
+    pub fn load(path: &Path) -> Result<Self> {
+        let loaded = load_versioned::<ActivityFile>(path, MIGRATIONS)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            events: loaded.value.map(|file| file.events).unwrap_or_default(),
+            corrupt_backup: loaded.corrupt_backup,
+        })
+    }
+
+    /// Where the file was moved if it was corrupted when loaded
+    pub fn corrupt_backup(&self) -> Option<&Path> {
+        self.corrupt_backup.as_deref()
+    }
+
+    pub fn events(&self) -> &[ActivityEvent] {
+        &self.events
+    }
+
+    pub fn record(&mut self, event: ActivityEvent) {
+        self.events.push(event);
+        let overflow = self.events.len().saturating_sub(MAX_EVENTS);
+        self.events.drain(..overflow);
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let file = ActivityFile {
+            version: MIGRATIONS.len() as u64,
+            events: self.events.clone(),
+        };
+        write_atomic(&self.path, serde_json::to_string(&file)?.as_bytes())
+    }
+}
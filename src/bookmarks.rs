@@ -0,0 +1,101 @@
+use crate::fs_utils::write_atomic;
+use crate::schema::{load_versioned, Migration};
+use crate::theme::{paint, Theme, Themed};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const MIGRATIONS: &[Migration] = &[];
+
+/// A comment or story kept to be found again, with enough of it to recall what it was
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub item: i64,
+    /// The story the item belongs to, the item itself for a story
+    pub story: i64,
+    /// The title of the story
+    pub title: String,
+    pub author: String,
+    /// The start of the text of the item
+    pub snippet: String,
+    /// The link to the item on HN
+    pub url: String,
+    /// When it was bookmarked, as a unix timestamp
+    pub saved_at: u64,
+}
+
+impl std::fmt::Display for Bookmark {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        Themed(self, &Theme::plain()).fmt(f)
+    }
+}
+
+impl std::fmt::Display for Themed<'_, Bookmark> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let Themed(bookmark, theme) = self;
+        write!(
+            f,
+            "{}: {}\n  in {} -> {}",
+            paint(&theme.author, &bookmark.author),
+            bookmark.snippet,
+            paint(&theme.title, &bookmark.title),
+            paint(&theme.url, &bookmark.url)
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct BookmarksFile {
+    version: u64,
+    #[serde(default)]
+    bookmarks: Vec<Bookmark>,
+}
+
+/// The bookmarks, oldest first
+pub struct Bookmarks {
+    path: PathBuf,
+    bookmarks: Vec<Bookmark>,
+    corrupt_backup: Option<PathBuf>,
+}
+
+impl Bookmarks {
+    /// Starts afresh when the file is missing or corrupted, the latter being moved aside
+    pub fn load(path: &Path) -> Result<Self> {
+        let loaded = load_versioned::<BookmarksFile>(path, MIGRATIONS)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            bookmarks: loaded.value.map(|file| file.bookmarks).unwrap_or_default(),
+            corrupt_backup: loaded.corrupt_backup,
+        })
+    }
+
+    /// Where the file was moved if it was corrupted when loaded
+    pub fn corrupt_backup(&self) -> Option<&Path> {
+        self.corrupt_backup.as_deref()
+    }
+
+    pub fn list(&self) -> &[Bookmark] {
+        &self.bookmarks
+    }
+
+    /// Adds the bookmark, replacing the one of the same item if any
+    pub fn add(&mut self, bookmark: Bookmark) {
+        self.remove(bookmark.item);
+        self.bookmarks.push(bookmark);
+    }
+
+    /// Returns whether the item was bookmarked
+    pub fn remove(&mut self, item: i64) -> bool {
+        let count = self.bookmarks.len();
+        self.bookmarks.retain(|bookmark| bookmark.item != item);
+        self.bookmarks.len() < count
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let file = BookmarksFile {
+            version: MIGRATIONS.len() as u64,
+            bookmarks: self.bookmarks.clone(),
+        };
+        write_atomic(&self.path, serde_json::to_string(&file)?.as_bytes())
+    }
+}
@@ -0,0 +1,444 @@
+use crate::html_utils::{decode_entities, extract_links};
+use crate::theme::{paint, Theme, Themed};
+use console::measure_text_width;
+use serde::Serialize;
+
+/// How a run of text is rendered, following the few tags HN allows
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "style", rename_all = "snake_case")]
+pub enum SpanStyle {
+    Plain,
+    /// `<i>` or `<em>`
+    Italic,
+    /// Inline `<code>`
+    Code,
+    Link {
+        href: String,
+    },
+    /// The "[N]" following a link, N being its position in the footnotes
+    Marker,
+    /// Text matching the pattern searched with `--grep`
+    Match,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Span {
+    pub text: String,
+    #[serde(flatten)]
+    pub style: SpanStyle,
+}
+
+impl Span {
+    pub fn plain(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            style: SpanStyle::Plain,
+        }
+    }
+}
+
+/// A block of a comment, as laid out by HN
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", content = "spans", rename_all = "snake_case")]
+pub enum Block {
+    Paragraph(Vec<Span>),
+    /// A paragraph starting with '>', the HN convention for quoting
+    Quote(Vec<Span>),
+    /// A `<pre><code>` block, its whitespace preserved
+    Code(String),
+}
+
+impl Block {
+    pub fn text(&self) -> String {
+        match self {
+            Block::Paragraph(spans) | Block::Quote(spans) => {
+                spans.iter().map(|span| span.text.as_str()).collect()
+            }
+            Block::Code(text) => text.to_string(),
+        }
+    }
+
+    fn take_lines(&self, max_lines: usize) -> Self {
+        match self {
+            Block::Paragraph(spans) => Block::Paragraph(take_lines(spans, max_lines)),
+            Block::Quote(spans) => Block::Quote(take_lines(spans, max_lines)),
+            Block::Code(text) => {
+                Block::Code(text.lines().take(max_lines).collect::<Vec<_>>().join("\n"))
+            }
+        }
+    }
+
+    fn write_themed(&self, f: &mut std::fmt::Formatter, theme: &Theme) -> std::fmt::Result {
+        let lines: Vec<String> = match self {
+            Block::Paragraph(spans) => {
+                let mut text = String::new();
+                for span in spans {
+                    let style = match &span.style {
+                        SpanStyle::Plain => "",
+                        SpanStyle::Italic => &theme.emphasis,
+                        SpanStyle::Code => &theme.code,
+                        SpanStyle::Link { .. } => &theme.url,
+                        SpanStyle::Marker => &theme.muted,
+                        SpanStyle::Match => &theme.highlight,
+                    };
+                    // painted line by line so that each line can be indented on its own
+                    let pieces: Vec<_> = span
+                        .text
+                        .split('\n')
+                        .map(|piece| paint(style, piece).to_string())
+                        .collect();
+                    text.push_str(&pieces.join("\n"));
+                }
+                text.lines().map(str::to_string).collect()
+            }
+            Block::Quote(_) => self
+                .text()
+                .lines()
+                .map(|line| paint(&theme.muted, format!("> {}", line)).to_string())
+                .collect(),
+            Block::Code(text) => text
+                .lines()
+                .map(|line| paint(&theme.code, line).to_string())
+                .collect(),
+        };
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// The text of a comment, parsed once when it is fetched
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CommentBody {
+    pub blocks: Vec<Block>,
+    /// The targets of the links, in order of appearance
+    pub links: Vec<String>,
+}
+
+impl CommentBody {
+    pub fn parse(html: &str) -> Self {
+        let mut blocks = Vec::new();
+        let mut link_count = 0;
+        let mut rest = html;
+        loop {
+            let (before, code) = match rest.find("<pre><code>") {
+                Some(start) => {
+                    let code = &rest[start + "<pre><code>".len()..];
+                    let end = code.find("</code></pre>").unwrap_or(code.len());
+                    let before = &rest[..start];
+                    rest = code[end..].trim_start_matches("</code></pre>");
+                    (before, Some(&code[..end]))
+                }
+                None => (rest, None),
+            };
+            blocks.extend(
+                before
+                    .split("<p>")
+                    .filter_map(|html| parse_paragraph(html, &mut link_count)),
+            );
+            match code {
+                Some(code) => {
+                    blocks.push(Block::Code(decode_entities(code).trim_end().to_string()))
+                }
+                None => break,
+            }
+        }
+        Self {
+            blocks,
+            links: extract_links(html),
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.to_string().lines().count()
+    }
+
+    /// Wraps the paragraphs and quotes to `width` columns, code blocks are left as they are
+    pub fn wrap(&mut self, width: usize) {
+        for block in &mut self.blocks {
+            *block = match block {
+                Block::Paragraph(spans) => Block::Paragraph(wrap_spans(spans, width)),
+                // room for the "> " prefix
+                Block::Quote(spans) => Block::Quote(wrap_spans(spans, width.saturating_sub(2))),
+                Block::Code(_) => continue,
+            };
+        }
+    }
+
+    /// The text to read aloud, without the "[N]" markers of the links nor the code blocks
+    pub fn spoken_text(&self) -> String {
+        let paragraphs: Vec<String> = self
+            .blocks
+            .iter()
+            .filter_map(|block| match block {
+                Block::Paragraph(spans) | Block::Quote(spans) => Some(
+                    spans
+                        .iter()
+                        .filter(|span| span.style != SpanStyle::Marker)
+                        .map(|span| span.text.as_str())
+                        .collect(),
+                ),
+                Block::Code(_) => None,
+            })
+            .collect();
+        paragraphs.join("\n\n")
+    }
+
+    /// Whether the pattern occurs where `highlight` can mark it: in the paragraphs and quotes,
+    /// possibly across styles, but neither in the link markers nor in the code blocks
+    pub fn contains(&self, pattern: &str) -> bool {
+        self.blocks.iter().any(|block| match block {
+            Block::Paragraph(spans) | Block::Quote(spans) => text_runs(spans).any(|run| {
+                let text: String = run.iter().map(|span| span.text.as_str()).collect();
+                find_ignore_case(&text, pattern).is_some()
+            }),
+            Block::Code(_) => false,
+        })
+    }
+
+    /// Marks the occurrences of the pattern in the paragraphs and quotes, ignoring case
+    pub fn highlight(&mut self, pattern: &str) {
+        for block in &mut self.blocks {
+            if let Block::Paragraph(spans) | Block::Quote(spans) = block {
+                let mut markers = spans.iter().filter(|span| span.style == SpanStyle::Marker);
+                let mut highlighted = Vec::new();
+                for (idx, run) in text_runs(spans).enumerate() {
+                    if idx > 0 {
+                        highlighted.extend(markers.next().cloned());
+                    }
+                    highlighted.extend(highlight_run(run, pattern));
+                }
+                *spans = highlighted;
+            }
+        }
+    }
+
+    /// Cuts the body after `max_lines` lines, ending it with the number of lines left out
+    pub fn truncate_lines(&mut self, max_lines: usize) {
+        let line_count = self.line_count();
+        if line_count <= max_lines {
+            return;
+        }
+        let mut remaining = max_lines;
+        let mut blocks = Vec::new();
+        for block in &self.blocks {
+            if remaining == 0 {
+                break;
+            }
+            let lines = block.text().lines().count();
+            if lines < remaining {
+                blocks.push(block.clone());
+                // the blank line between two blocks
+                remaining -= lines + 1;
+            } else {
+                blocks.push(block.take_lines(remaining));
+                remaining = 0;
+            }
+        }
+        let shown = max_lines - remaining;
+        let more = format!("[... {} more lines]", line_count - shown);
+        blocks.push(Block::Paragraph(vec![Span::plain(&more)]));
+        self.blocks = blocks;
+    }
+}
+
+fn parse_paragraph(html: &str, link_count: &mut usize) -> Option<Block> {
+    let mut spans = parse_spans(html, link_count);
+    if let Some(first) = spans.first_mut() {
+        first.text = first.text.trim_start().to_string();
+    }
+    if let Some(last) = spans.last_mut() {
+        last.text = last.text.trim_end().to_string();
+    }
+    spans.retain(|span| !span.text.is_empty());
+    let first = spans.first_mut()?;
+    match first.text.strip_prefix('>') {
+        Some(quote) => {
+            first.text = quote.trim_start().to_string();
+            spans.retain(|span| !span.text.is_empty());
+            Some(Block::Quote(spans))
+        }
+        None => Some(Block::Paragraph(spans)),
+    }
+}
+
+/// Splits the HTML of a paragraph into runs of text, the tags setting their style,
+/// each link being followed by its marker
+fn parse_spans(html: &str, link_count: &mut usize) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut style = SpanStyle::Plain;
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        push_text(&mut spans, &decode_entities(&rest[..start]), &style);
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+        let name = tag.split_whitespace().next().unwrap_or_default();
+        let next_style = match name.to_ascii_lowercase().as_str() {
+            "i" | "em" => SpanStyle::Italic,
+            "code" => SpanStyle::Code,
+            "a" => match extract_links(tag).into_iter().next() {
+                Some(href) => SpanStyle::Link { href },
+                None => style.clone(),
+            },
+            "/i" | "/em" | "/code" | "/a" => SpanStyle::Plain,
+            _ => style.clone(),
+        };
+        if matches!(style, SpanStyle::Link { .. }) && next_style != style {
+            push_marker(&mut spans, link_count);
+        }
+        style = next_style;
+    }
+    push_text(&mut spans, &decode_entities(rest), &style);
+    if matches!(style, SpanStyle::Link { .. }) {
+        push_marker(&mut spans, link_count);
+    }
+    spans
+}
+
+fn push_marker(spans: &mut Vec<Span>, link_count: &mut usize) {
+    *link_count += 1;
+    spans.push(Span {
+        text: format!("[{}]", link_count),
+        style: SpanStyle::Marker,
+    });
+}
+
+/// Appends text to the last span when it has the same style
+fn push_text(spans: &mut Vec<Span>, text: &str, style: &SpanStyle) {
+    if text.is_empty() {
+        return;
+    }
+    match spans.last_mut() {
+        Some(last) if &last.style == style => last.text.push_str(text),
+        _ => spans.push(Span {
+            text: text.to_string(),
+            style: style.clone(),
+        }),
+    }
+}
+
+/// The start and end of the first occurrence of the pattern, ignoring case
+pub(crate) fn find_ignore_case(text: &str, pattern: &str) -> Option<(usize, usize)> {
+    if pattern.is_empty() {
+        return None;
+    }
+    text.char_indices().find_map(|(start, _)| {
+        let mut end = start;
+        let mut chars = text[start..].chars();
+        let found = pattern.chars().all(|expected| {
+            chars.next().is_some_and(|c| {
+                end += c.len_utf8();
+                c.to_lowercase().eq(expected.to_lowercase())
+            })
+        });
+        found.then_some((start, end))
+    })
+}
+
+/// The runs of text between the link markers, a match never spanning a marker
+fn text_runs(spans: &[Span]) -> impl Iterator<Item = &[Span]> {
+    spans.split(|span| span.style == SpanStyle::Marker)
+}
+
+/// Splits the spans of a run around the occurrences of the pattern, an occurrence running
+/// over several spans being marked as a whole
+fn highlight_run(run: &[Span], pattern: &str) -> Vec<Span> {
+    let text: String = run.iter().map(|span| span.text.as_str()).collect();
+    let mut matches = Vec::new();
+    let mut offset = 0;
+    while let Some((start, end)) = find_ignore_case(&text[offset..], pattern) {
+        matches.push((offset + start, offset + end));
+        offset += end;
+    }
+    let mut spans = Vec::new();
+    let mut span_start = 0;
+    for span in run {
+        let span_end = span_start + span.text.len();
+        let mut cut = span_start;
+        for (start, end) in matches.iter().copied() {
+            if start >= span_end || end <= span_start {
+                continue;
+            }
+            let (start, end) = (start.max(span_start), end.min(span_end));
+            push_text(&mut spans, &text[cut..start], &span.style);
+            push_text(&mut spans, &text[start..end], &SpanStyle::Match);
+            cut = end;
+        }
+        push_text(&mut spans, &text[cut..span_end], &span.style);
+        span_start = span_end;
+    }
+    spans
+}
+
+/// Keeps the first `max_lines` lines of the spans
+fn take_lines(spans: &[Span], max_lines: usize) -> Vec<Span> {
+    let mut taken = Vec::new();
+    let mut lines = 1;
+    for span in spans {
+        let mut text = String::new();
+        for (idx, piece) in span.text.split('\n').enumerate() {
+            if idx > 0 {
+                if lines == max_lines {
+                    push_text(&mut taken, &text, &span.style);
+                    return taken;
+                }
+                lines += 1;
+                text.push('\n');
+            }
+            text.push_str(piece);
+        }
+        push_text(&mut taken, &text, &span.style);
+    }
+    taken
+}
+
+/// Breaks the lines between words so that they fit in `width` columns when possible,
+/// a word longer than the width getting a line of its own
+fn wrap_spans(spans: &[Span], width: usize) -> Vec<Span> {
+    let width = width.max(1);
+    let mut wrapped = Vec::new();
+    let mut line_width = 0;
+    let mut pending_space = false;
+    for span in spans {
+        pending_space |= span.text.starts_with(char::is_whitespace);
+        let words: Vec<_> = span.text.split_whitespace().collect();
+        for (idx, word) in words.iter().enumerate() {
+            let word_width = measure_text_width(word);
+            if line_width > 0 && (pending_space || idx > 0) {
+                if line_width + 1 + word_width > width {
+                    push_text(&mut wrapped, "\n", &SpanStyle::Plain);
+                    line_width = 0;
+                } else {
+                    push_text(&mut wrapped, " ", &SpanStyle::Plain);
+                    line_width += 1;
+                }
+            }
+            push_text(&mut wrapped, word, &span.style);
+            line_width += word_width;
+            pending_space = false;
+        }
+        pending_space |= !words.is_empty() && span.text.ends_with(char::is_whitespace);
+    }
+    wrapped
+}
+
+impl std::fmt::Display for CommentBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        Themed(self, &Theme::plain()).fmt(f)
+    }
+}
+
+impl std::fmt::Display for Themed<'_, CommentBody> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let Themed(body, theme) = self;
+        for (idx, block) in body.blocks.iter().enumerate() {
+            if idx > 0 {
+                write!(f, "\n\n")?;
+            }
+            block.write_themed(f, theme)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,659 @@
+use crate::comment_body::{find_ignore_case, CommentBody};
+use crate::config::CollapseRules;
+use crate::flair::UserFlair;
+use crate::html_utils::snippet;
+use crate::theme::{paint, Theme, Themed};
+use crate::time_utils::now;
+use crate::HNCLIItem;
+use serde::Serialize;
+use std::collections::HashMap;
+
+const INDENT: &str = "  ";
+const MIN_WRAP_WIDTH: usize = 40;
+/// Enough of the parent of a comment listed by `--flat` to recall it
+const PARENT_SNIPPET_CHARS: usize = 80;
+/// The replies collapsed by the `low_effort` rule, once lowercased and stripped of punctuation
+const LOW_EFFORT_REPLIES: &[&str] = &[
+    "+1", "this", "same", "agreed", "exactly", "metoo", "thanks", "thankyou", "yes", "lol",
+];
+
+#[derive(Debug, Serialize)]
+pub struct HNCLIComment {
+    pub id: i64,
+    pub author: String,
+    pub body: CommentBody,
+    pub time_ago: String,
+    pub timestamp: u64,
+    pub deleted: bool,
+    /// Killed by moderators or flags, the text being kept by the API
+    pub dead: bool,
+    /// Shown as a single line, its text and replies left out
+    pub collapsed: bool,
+    pub reply_count: usize,
+    pub replies: Vec<HNCLIComment>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HNCLIThread {
+    pub story: HNCLIItem,
+    /// The story and the comments above the item when it is a comment, the story first
+    pub breadcrumb: Vec<Crumb>,
+    /// The body of Ask HN and other self posts
+    pub text: Option<CommentBody>,
+    /// The earlier submissions of the same URL, only looked up on demand
+    pub previous: Vec<HNCLIItem>,
+    /// The flairs of the comment authors, only looked up on demand
+    pub flairs: HashMap<String, UserFlair>,
+    /// The comments fetched out of each level
+    pub window: CommentWindow,
+    /// The number of top level comments, fetched or not
+    pub comment_count: usize,
+    /// Lists the comments by date, each one naming the author it replies to, instead of as
+    /// a tree
+    #[serde(skip)]
+    pub flat: bool,
+    /// The comments posted after this timestamp, the previous visit of the thread, are
+    /// marked as new
+    #[serde(skip)]
+    pub new_since: Option<u64>,
+    /// The window continues from the saved reading progress, so the next one is fetched
+    /// with --resume rather than a skip
+    #[serde(skip)]
+    pub resumed: bool,
+    pub comments: Vec<HNCLIComment>,
+}
+
+/// A link found in a thread, with who posted it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ThreadLink {
+    pub url: String,
+    pub author: String,
+    /// The id of the comment or story whose text holds the link
+    pub item: i64,
+}
+
+/// An item on the way from a story down to a comment
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Crumb {
+    pub id: i64,
+    /// The title of a story or the author of a comment
+    pub label: String,
+}
+
+/// Which comments of each level are fetched, to keep huge threads bounded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct CommentWindow {
+    /// The number of top level comments skipped
+    pub skip: usize,
+    /// The number of comments fetched per level, all of them when not set
+    pub limit: Option<usize>,
+    /// Starts after this top level comment instead of skipping a number of them, e.g. the
+    /// last one read, so that newly ranked comments do not shift the position
+    pub after: Option<i64>,
+}
+
+impl CommentWindow {
+    /// Turns `after` into the number of top level comments to skip, the comment being
+    /// looked up among them. The skip is kept when it is not found, e.g. deleted since
+    pub fn resolve(self, ids: &[i64]) -> Self {
+        let position = self
+            .after
+            .and_then(|after| ids.iter().position(|id| *id == after));
+        Self {
+            skip: position.map_or(self.skip, |position| position + 1),
+            after: None,
+            ..self
+        }
+    }
+
+    /// The ids of a level that fall in the window, the first level being the only one skipped
+    pub fn apply<'a>(&self, ids: &'a [i64], top_level: bool) -> &'a [i64] {
+        let skip = match top_level {
+            true => self.skip.min(ids.len()),
+            false => 0,
+        };
+        let ids = &ids[skip..];
+        &ids[..self.limit.unwrap_or(ids.len()).min(ids.len())]
+    }
+}
+
+/// A comment on its own, as streamed while a thread is being fetched
+#[derive(Debug, Serialize)]
+pub struct HNCLICommentRecord {
+    pub parent: i64,
+    pub depth: u8,
+    #[serde(flatten)]
+    pub comment: HNCLIComment,
+}
+
+/// The story first, then its comments level by level
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum ThreadEntry {
+    Story(HNCLIItem),
+    Comment(HNCLICommentRecord),
+}
+
+impl HNCLIComment {
+    /// Cuts the texts longer than `max_lines`, e.g. pasted logs, in the comment and its replies
+    pub fn truncate_lines(&mut self, max_lines: usize) {
+        self.body.truncate_lines(max_lines);
+        for reply in &mut self.replies {
+            reply.truncate_lines(max_lines);
+        }
+    }
+
+    /// Collapses the comment and its replies matching one of the rules
+    pub fn collapse(&mut self, rules: &CollapseRules) {
+        self.collapsed = self.is_noise(rules);
+        for reply in &mut self.replies {
+            reply.collapse(rules);
+        }
+    }
+
+    fn is_noise(&self, rules: &CollapseRules) -> bool {
+        if self.deleted {
+            return rules.deleted && self.is_deleted_subtree();
+        }
+        let text = self.body.to_string();
+        let normalized: String = text
+            .to_lowercase()
+            .chars()
+            .filter(|c| c.is_alphanumeric() || *c == '+')
+            .collect();
+        rules.muted_users.contains(&self.author)
+            || rules
+                .min_length
+                .is_some_and(|min_length| text.chars().count() < min_length)
+            || (rules.low_effort && LOW_EFFORT_REPLIES.contains(&normalized.as_str()))
+    }
+
+    fn is_deleted_subtree(&self) -> bool {
+        self.deleted && self.replies.iter().all(HNCLIComment::is_deleted_subtree)
+    }
+
+    /// Turns the dead comments and replies into deleted ones, as HN shows them by default
+    pub fn hide_dead(&mut self) {
+        if self.dead {
+            self.deleted = true;
+            self.body = CommentBody::default();
+        }
+        for reply in &mut self.replies {
+            reply.hide_dead();
+        }
+    }
+
+    /// Wraps the texts of the comment and its replies to fit in `width` columns once indented
+    pub fn wrap(&mut self, width: usize) {
+        // keeps deep replies readable instead of squeezing them to a few characters
+        self.body.wrap(width.max(MIN_WRAP_WIDTH));
+        for reply in &mut self.replies {
+            reply.wrap(width.saturating_sub(INDENT.len()));
+        }
+    }
+
+    /// The number of replies at any depth, as far as they were fetched, the unfetched ones
+    /// only being known by their number of direct replies
+    pub fn descendant_count(&self) -> usize {
+        let unfetched = self.reply_count.saturating_sub(self.replies.len());
+        let fetched: usize = self
+            .replies
+            .iter()
+            .map(|reply| 1 + reply.descendant_count())
+            .sum();
+        unfetched + fetched
+    }
+
+    /// Whether some replies at any depth were not fetched, making `descendant_count` a
+    /// lower bound
+    fn has_unfetched_replies(&self) -> bool {
+        self.replies.len() < self.reply_count
+            || self.replies.iter().any(HNCLIComment::has_unfetched_replies)
+    }
+
+    /// The author, or "[deleted]" when the comment was deleted
+    fn shown_author(&self) -> &str {
+        match self.deleted {
+            true => "[deleted]",
+            false => &self.author,
+        }
+    }
+
+    /// The authors of the comment and its replies, each one once
+    pub fn authors(&self) -> Vec<&str> {
+        let mut authors = vec![self.author.as_str()];
+        for reply in &self.replies {
+            for author in reply.authors() {
+                if !authors.contains(&author) {
+                    authors.push(author);
+                }
+            }
+        }
+        authors.retain(|author| !author.is_empty());
+        authors
+    }
+
+    /// Writes the comment and its replies, the thread giving the story author for the "OP"
+    /// badge and the flairs of the authors
+    fn write_tree(
+        &self,
+        f: &mut impl std::fmt::Write,
+        depth: usize,
+        thread: Option<&HNCLIThread>,
+        theme: &Theme,
+    ) -> std::fmt::Result {
+        let indent = INDENT.repeat(depth);
+        self.write_entry(f, &indent, None, thread, theme)?;
+        if self.collapsed {
+            return Ok(());
+        }
+        // replies beyond the fetched depth are only counted
+        if self.replies.is_empty() && self.reply_count > 0 {
+            let more = format!("[+{} replies]", self.reply_count);
+            writeln!(f, "{}  {}", indent, paint(&theme.muted, more))?;
+        }
+        for reply in &self.replies {
+            writeln!(f)?;
+            reply.write_tree(f, depth + 1, thread, theme)?;
+        }
+        // replies beyond the window, with the command fetching the next ones
+        let remaining = self.reply_count.saturating_sub(self.replies.len());
+        if let Some(limit) = thread.and_then(|thread| thread.window.limit) {
+            if !self.replies.is_empty() && remaining > 0 {
+                let more = format!(
+                    "[+{} more replies: hn {} --skip {} --per-level {}]",
+                    remaining, self.id, limit, limit
+                );
+                writeln!(f, "\n{}  {}", indent, paint(&theme.muted, more))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the comment without its replies, the comment it replies to being named with
+    /// the start of its text when given
+    fn write_entry(
+        &self,
+        f: &mut impl std::fmt::Write,
+        indent: &str,
+        parent: Option<&HNCLIComment>,
+        thread: Option<&HNCLIThread>,
+        theme: &Theme,
+    ) -> std::fmt::Result {
+        let op = thread.map(|thread| thread.story.author.as_str());
+        let reply_to = match parent {
+            Some(parent) => format!(
+                " - {}",
+                paint(
+                    &theme.muted,
+                    format!("in reply to {}", parent.shown_author())
+                )
+            ),
+            None => String::new(),
+        };
+        if self.collapsed {
+            let author = self.shown_author();
+            let descendants = self.descendant_count();
+            let more = match descendants > self.reply_count {
+                true => {
+                    let bound = if self.has_unfetched_replies() {
+                        "+"
+                    } else {
+                        ""
+                    };
+                    format!(
+                        "[collapsed, {} replies, {}{} in all]",
+                        self.reply_count, descendants, bound
+                    )
+                }
+                false => format!("[collapsed, {} replies]", self.reply_count),
+            };
+            return writeln!(
+                f,
+                "{}{}{}",
+                indent,
+                paint(&theme.muted, format!("{} {}", author, more)),
+                reply_to
+            );
+        }
+        if self.deleted {
+            writeln!(
+                f,
+                "{}{}{}",
+                indent,
+                paint(&theme.muted, "[deleted]"),
+                reply_to
+            )?;
+        } else {
+            let mut badge = match op == Some(self.author.as_str()) {
+                true => format!(" {}", paint(&theme.op, "[OP]")),
+                false => String::new(),
+            };
+            if let Some(flair) = thread.and_then(|thread| thread.flairs.get(&self.author)) {
+                let flair = format!("({}, {})", flair.karma_bucket(), flair.account_age(now()));
+                badge.push_str(&format!(" {}", paint(&theme.muted, flair)));
+            }
+            if self.dead {
+                badge.push_str(&format!(" {}", paint(&theme.muted, "[dead]")));
+            }
+            if thread.is_some_and(|thread| thread.is_new(self)) {
+                badge.push_str(&format!(" {}", paint(&theme.highlight, "[new]")));
+            }
+            writeln!(
+                f,
+                "{}{}{} - {}{}",
+                indent,
+                paint(theme.comment_author_style(&self.author), &self.author),
+                badge,
+                paint(&theme.muted, &self.time_ago),
+                reply_to
+            )?;
+            // the parent may be far above, its first words tell what the reply is about
+            if let Some(parent) = parent.filter(|parent| !parent.deleted) {
+                let text = parent.body.to_string();
+                let words: Vec<_> = text.split_whitespace().collect();
+                let context = format!("^ {}", snippet(&words.join(" "), PARENT_SNIPPET_CHARS));
+                writeln!(f, "{}{}", indent, paint(&theme.muted, context))?;
+            }
+            for line in Themed(&self.body, theme).to_string().lines() {
+                match self.dead {
+                    // dimmed as a whole, the styles of the text are left out
+                    true => writeln!(
+                        f,
+                        "{}{}",
+                        indent,
+                        paint(&theme.muted, console::strip_ansi_codes(line))
+                    )?,
+                    false => writeln!(f, "{}{}", indent, line)?,
+                }
+            }
+            write_footnotes(f, &self.body.links, indent, theme)?;
+        }
+        Ok(())
+    }
+}
+
+impl HNCLIThread {
+    /// Wraps the self text and the comments to fit in `width` columns, a terminal narrower
+    /// than a few words, e.g. a tmux split, being treated as a wider one
+    pub fn wrap(&mut self, width: usize) {
+        if let Some(text) = &mut self.text {
+            text.wrap(width.max(MIN_WRAP_WIDTH));
+        }
+        match self.flat {
+            // not indented, every comment has the whole width
+            true => wrap_unindented(&mut self.comments, width),
+            false => self
+                .comments
+                .iter_mut()
+                .for_each(|comment| comment.wrap(width)),
+        }
+    }
+
+    /// The links of the self text and of the comments, each one once, in the order of the
+    /// thread
+    pub fn links(&self) -> Vec<ThreadLink> {
+        fn push(comments: &[HNCLIComment], links: &mut Vec<ThreadLink>) {
+            for comment in comments {
+                push_links(links, &comment.body, &comment.author, comment.id);
+                push(&comment.replies, links);
+            }
+        }
+        let mut links = Vec::new();
+        if let Some(text) = &self.text {
+            push_links(&mut links, text, &self.story.author, self.story.id);
+        }
+        push(&self.comments, &mut links);
+        links
+    }
+
+    /// The number of comments posted since the previous visit, as far as they were fetched
+    pub fn new_count(&self) -> usize {
+        fn count(thread: &HNCLIThread, comments: &[HNCLIComment]) -> usize {
+            (comments.iter())
+                .map(|comment| {
+                    usize::from(thread.is_new(comment)) + count(thread, &comment.replies)
+                })
+                .sum()
+        }
+        count(self, &self.comments)
+    }
+
+    fn is_new(&self, comment: &HNCLIComment) -> bool {
+        let since = self.new_since.unwrap_or(u64::MAX);
+        !comment.deleted && comment.timestamp > since
+    }
+
+    /// The title and self text of the story then its top level comments, as read by `--speak`
+    pub fn spoken_text(&self) -> String {
+        let mut parts = vec![format!("{}, by {}.", self.story.title, self.story.author)];
+        parts.extend(self.text.as_ref().map(CommentBody::spoken_text));
+        for comment in &self.comments {
+            if !comment.deleted && !comment.collapsed {
+                let text = comment.body.spoken_text();
+                parts.push(format!("{} says: {}", comment.author, text));
+            }
+        }
+        parts.join("\n\n")
+    }
+
+    /// The comments and the ones they reply to, oldest first, the replies of the collapsed
+    /// comments left out
+    fn flatten(&self) -> Vec<(&HNCLIComment, Option<&HNCLIComment>)> {
+        fn push<'a>(
+            comments: &'a [HNCLIComment],
+            parent: Option<&'a HNCLIComment>,
+            flat: &mut Vec<(&'a HNCLIComment, Option<&'a HNCLIComment>)>,
+        ) {
+            for comment in comments {
+                flat.push((comment, parent));
+                if !comment.collapsed {
+                    push(&comment.replies, Some(comment), flat);
+                }
+            }
+        }
+        let mut flat = Vec::new();
+        push(&self.comments, None, &mut flat);
+        // stable, so that the comments of the same second keep the order of the tree
+        flat.sort_by_key(|(comment, _)| comment.timestamp);
+        flat
+    }
+}
+
+fn push_links(links: &mut Vec<ThreadLink>, body: &CommentBody, author: &str, item: i64) {
+    for url in &body.links {
+        if !links.iter().any(|link| &link.url == url) {
+            links.push(ThreadLink {
+                url: url.to_string(),
+                author: author.to_string(),
+                item,
+            });
+        }
+    }
+}
+
+fn wrap_unindented(comments: &mut [HNCLIComment], width: usize) {
+    for comment in comments {
+        comment.body.wrap(width.max(MIN_WRAP_WIDTH));
+        wrap_unindented(&mut comment.replies, width);
+    }
+}
+
+/// Drops the deleted comments that have no reply left to show, deepest ones first
+pub fn prune_deleted(comments: &mut Vec<HNCLIComment>) {
+    comments.retain_mut(|comment| {
+        // the replies beyond the fetched depth are unknown, they may not be deleted
+        let unfetched = comment.replies.is_empty() && comment.reply_count > 0;
+        let fetched = comment.replies.len();
+        prune_deleted(&mut comment.replies);
+        comment.reply_count = comment
+            .reply_count
+            .saturating_sub(fetched - comment.replies.len());
+        !comment.deleted || unfetched || !comment.replies.is_empty()
+    });
+}
+
+/// Keeps the comments matching the pattern, by text or author, and the ones they reply to,
+/// the matches highlighted and expanded. Returns the number of matching comments
+pub fn grep_comments(comments: &mut Vec<HNCLIComment>, pattern: &str) -> usize {
+    retain_branches(comments, &|comment| {
+        let matched =
+            find_ignore_case(&comment.author, pattern).is_some() || comment.body.contains(pattern);
+        if matched {
+            comment.body.highlight(pattern);
+        }
+        matched
+    })
+}
+
+/// Keeps the comments written by a user and the ones they reply to, expanded. Returns the
+/// number of comments of the user
+pub fn filter_by_author(comments: &mut Vec<HNCLIComment>, author: &str) -> usize {
+    retain_branches(comments, &|comment| comment.author == author)
+}
+
+/// Keeps the comments accepted by `keep` and their ancestors, expanding them, and returns
+/// the number of accepted comments
+fn retain_branches(
+    comments: &mut Vec<HNCLIComment>,
+    keep: &dyn Fn(&mut HNCLIComment) -> bool,
+) -> usize {
+    let mut count = 0;
+    comments.retain_mut(|comment| {
+        let replies = retain_branches(&mut comment.replies, keep);
+        let kept = keep(comment);
+        count += replies + usize::from(kept);
+        comment.collapsed &= !kept && replies == 0;
+        kept || replies > 0
+    });
+    count
+}
+
+/// Lists the targets of the "[N]" markers under a text
+fn write_footnotes(
+    f: &mut impl std::fmt::Write,
+    links: &[String],
+    indent: &str,
+    theme: &Theme,
+) -> std::fmt::Result {
+    if !links.is_empty() {
+        writeln!(f)?;
+    }
+    for (idx, link) in links.iter().enumerate() {
+        let marker = format!("[{}]", idx + 1);
+        writeln!(
+            f,
+            "{}{} {}",
+            indent,
+            paint(&theme.muted, marker),
+            paint(&theme.url, link)
+        )?;
+    }
+    Ok(())
+}
+
+impl std::fmt::Display for HNCLIComment {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.write_tree(f, 0, None, &Theme::plain())
+    }
+}
+
+impl std::fmt::Display for ThreadLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        Themed(self, &Theme::plain()).fmt(f)
+    }
+}
+
+impl std::fmt::Display for Themed<'_, ThreadLink> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let Themed(link, theme) = self;
+        write!(
+            f,
+            "{} - {}",
+            paint(&theme.url, &link.url),
+            paint(&theme.author, &link.author)
+        )
+    }
+}
+
+impl std::fmt::Display for HNCLIThread {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        Themed(self, &Theme::plain()).fmt(f)
+    }
+}
+
+impl std::fmt::Display for Themed<'_, HNCLIThread> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let Themed(thread, theme) = self;
+        write!(f, "{}", Themed(&thread.story, theme))?;
+        if let Some(parent) = thread.breadcrumb.last() {
+            let labels: Vec<_> = thread
+                .breadcrumb
+                .iter()
+                .map(|crumb| crumb.label.as_str())
+                .collect();
+            let path = format!("in {}", labels.join(" > "));
+            let up = format!("[up: hn {}]", parent.id);
+            write!(
+                f,
+                "\n{}\n{}",
+                paint(&theme.muted, path),
+                paint(&theme.muted, up)
+            )?;
+        }
+        if !thread.previous.is_empty() {
+            let count = format!("previously discussed: {} threads", thread.previous.len());
+            write!(f, "\n{}", paint(&theme.muted, count))?;
+        }
+        for previous in &thread.previous {
+            let points = previous.score.unwrap_or_default();
+            let comments = previous.comments.unwrap_or_default();
+            let details = format!(
+                "{} - {} points - {} comments",
+                previous.time, points, comments
+            );
+            write!(
+                f,
+                "\n  {} -> {}",
+                paint(&theme.details, details),
+                paint(&theme.url, &previous.url)
+            )?;
+        }
+        if let Some(text) = &thread.text {
+            let mut body = format!("{}\n", Themed(text, theme));
+            write_footnotes(&mut body, &text.links, "", theme)?;
+            write!(f, "\n\n{}", body.trim_end())?;
+        }
+        if thread.flat {
+            for (comment, parent) in thread.flatten() {
+                let mut entry = String::new();
+                comment.write_entry(&mut entry, "", parent, Some(thread), theme)?;
+                write!(f, "\n\n{}", entry.trim_end())?;
+            }
+        } else {
+            for comment in &thread.comments {
+                let mut tree = String::new();
+                comment.write_tree(&mut tree, 0, Some(thread), theme)?;
+                write!(f, "\n\n{}", tree.trim_end())?;
+            }
+        }
+        if let Some(limit) = thread.window.limit {
+            let next = thread.window.skip + limit;
+            if next < thread.comment_count {
+                let from = match thread.resumed {
+                    true => "--resume".to_string(),
+                    false => format!("--skip {}", next),
+                };
+                let more = format!(
+                    "[+{} more comments: hn {} {} --per-level {}]",
+                    thread.comment_count - next,
+                    thread.story.id,
+                    from,
+                    limit
+                );
+                write!(f, "\n\n{}", paint(&theme.muted, more))?;
+            }
+        }
+        Ok(())
+    }
+}
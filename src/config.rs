@@ -0,0 +1,125 @@
+use crate::theme::BUILTIN_THEMES;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Defaults read from `config.toml`, the flags given on the command line take precedence
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The story type listed when no command is given
+    pub story_type: Option<String>,
+    /// The number of stories per page
+    pub page_size: Option<u8>,
+    /// The number of comment levels to retrieve, also accepted as `auto_expand_depth`
+    #[serde(alias = "auto_expand_depth")]
+    pub comment_depth: Option<u8>,
+    /// One of the bundled themes, used instead of `theme.toml`
+    pub theme: Option<String>,
+    /// The base URL of the HN API
+    pub api_url: Option<String>,
+    /// Show the text of the comments killed by moderators or flags
+    pub show_dead: Option<bool>,
+    /// Leave out the deleted comments that have no reply
+    pub hide_deleted: Option<bool>,
+    /// The text-to-speech command `--speak` writes to, e.g. "espeak" or "say -v Samantha"
+    pub tts_command: Option<String>,
+    /// The comments shown collapsed, to a single line
+    pub collapse: CollapseRules,
+}
+
+/// What makes a comment noise, each rule collapsing the comments it matches
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CollapseRules {
+    /// Comments with a text shorter than this number of characters
+    pub min_length: Option<usize>,
+    /// Comments of these users
+    pub muted_users: Vec<String>,
+    /// Deleted comments whose replies are all deleted too
+    pub deleted: bool,
+    /// Replies saying nothing more than "+1" or "this"
+    pub low_effort: bool,
+}
+
+impl Config {
+    /// Reads the configuration, a missing file meaning that nothing is configured
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => {
+                Self::parse(&content).with_context(|| format!("Invalid `{}`", path.display()))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Could not read `{}`", path.display())),
+        }
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let config: Self = toml::from_str(content)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Layers the `HN_CLI_*` variables over the file, e.g. `HN_CLI_PAGE_SIZE` over `page_size`
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<()> {
+        if let Some(story_type) = var("HN_CLI_STORY_TYPE") {
+            self.story_type = Some(story_type);
+        }
+        if let Some(page_size) = var("HN_CLI_PAGE_SIZE") {
+            self.page_size = Some(
+                page_size
+                    .parse()
+                    .with_context(|| format!("Invalid HN_CLI_PAGE_SIZE: {}", page_size))?,
+            );
+        }
+        if let Some(depth) = var("HN_CLI_COMMENT_DEPTH") {
+            self.comment_depth = Some(
+                depth
+                    .parse()
+                    .with_context(|| format!("Invalid HN_CLI_COMMENT_DEPTH: {}", depth))?,
+            );
+        }
+        if let Some(theme) = var("HN_CLI_THEME") {
+            self.theme = Some(theme);
+        }
+        if let Some(api_url) = var("HN_CLI_API_URL") {
+            self.api_url = Some(api_url);
+        }
+        if let Some(show_dead) = var("HN_CLI_SHOW_DEAD") {
+            self.show_dead = Some(
+                show_dead
+                    .parse()
+                    .with_context(|| format!("Invalid HN_CLI_SHOW_DEAD: {}", show_dead))?,
+            );
+        }
+        if let Some(hide_deleted) = var("HN_CLI_HIDE_DELETED") {
+            self.hide_deleted = Some(
+                hide_deleted
+                    .parse()
+                    .with_context(|| format!("Invalid HN_CLI_HIDE_DELETED: {}", hide_deleted))?,
+            );
+        }
+        if let Some(tts_command) = var("HN_CLI_TTS_COMMAND") {
+            self.tts_command = Some(tts_command);
+        }
+        self.validate()
+    }
+
+    fn validate(&self) -> Result<()> {
+        if let Some(page_size) = self.page_size {
+            if !(1..=50).contains(&page_size) {
+                bail!("page_size should be between 1 and 50 inclusive");
+            }
+        }
+        if let Some(theme) = &self.theme {
+            if !BUILTIN_THEMES.contains(&theme.as_str()) {
+                bail!(
+                    "Unknown theme {}, should be one of {}",
+                    theme,
+                    BUILTIN_THEMES.join(", ")
+                );
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,32 @@
+use crate::HNCLIItem;
+
+pub fn items_to_csv<'a>(items: impl IntoIterator<Item = &'a HNCLIItem>) -> String {
+    let mut csv = String::from("id,title,url,author,score,comments,time\n");
+    for item in items {
+        let row = [
+            item.id.to_string(),
+            csv_field(&item.title),
+            csv_field(&item.url),
+            csv_field(&item.author),
+            item.score
+                .map(|score| score.to_string())
+                .unwrap_or_default(),
+            item.comments
+                .map(|comments| comments.to_string())
+                .unwrap_or_default(),
+            item.time.to_string(),
+        ];
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Quotes the value if needed, doubling its quotes as described in RFC 4180
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
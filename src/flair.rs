@@ -0,0 +1,101 @@
+use crate::fs_utils::write_atomic;
+use crate::schema::{load_versioned, Migration};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Karma and account age change slowly, a day old profile is good enough
+const MAX_AGE_SECS: u64 = 86400;
+
+const MIGRATIONS: &[Migration] = &[];
+
+const SECS_PER_MONTH: u64 = 2629746;
+
+/// What is shown next to a comment author to tell who they are
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct UserFlair {
+    pub karma: i32,
+    /// When the account was created, as a unix timestamp
+    pub created: u64,
+}
+
+impl UserFlair {
+    /// A coarse karma, e.g. "10k+", the exact number telling nothing more
+    pub fn karma_bucket(&self) -> String {
+        match self.karma {
+            ..=99 => "<100".to_string(),
+            100..=999 => format!("{}+", self.karma / 100 * 100),
+            1000..=9999 => format!("{}k+", self.karma / 1000),
+            _ => format!("{}k+", self.karma / 10000 * 10),
+        }
+    }
+
+    /// The age of the account at `now`, in months for the first year and in years after
+    pub fn account_age(&self, now: u64) -> String {
+        let months = now.saturating_sub(self.created) / SECS_PER_MONTH;
+        match months {
+            0..=11 => format!("{}mo", months),
+            _ => format!("{}y", months / 12),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CachedFlair {
+    #[serde(flatten)]
+    flair: UserFlair,
+    fetched_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FlairCacheFile {
+    version: u64,
+    #[serde(default)]
+    users: HashMap<String, CachedFlair>,
+}
+
+/// The flairs of the users already looked up, kept between runs
+pub struct FlairCache {
+    path: PathBuf,
+    users: HashMap<String, CachedFlair>,
+}
+
+impl FlairCache {
+    /// Starts afresh when the file is missing or corrupted, the latter being moved aside. It
+    /// is only a cache, that the next save overwrites
+    pub fn load(path: &Path) -> Result<Self> {
+        let loaded = load_versioned::<FlairCacheFile>(path, MIGRATIONS)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            users: loaded.value.map(|file| file.users).unwrap_or_default(),
+        })
+    }
+
+    /// The flair of a user, unless it was never fetched or is older than a day
+    pub fn get(&self, username: &str, now: u64) -> Option<UserFlair> {
+        self.users
+            .get(username)
+            .filter(|cached| now.saturating_sub(cached.fetched_at) < MAX_AGE_SECS)
+            .map(|cached| cached.flair)
+    }
+
+    pub fn insert(&mut self, username: &str, flair: UserFlair, now: u64) {
+        let cached = CachedFlair {
+            flair,
+            fetched_at: now,
+        };
+        self.users.insert(username.to_string(), cached);
+    }
+
+    /// Writes the cache, the expired entries left out
+    pub fn save(&mut self, now: u64) -> Result<()> {
+        self.users
+            .retain(|_, cached| now.saturating_sub(cached.fetched_at) < MAX_AGE_SECS);
+        let file = FlairCacheFile {
+            version: MIGRATIONS.len() as u64,
+            users: self.users.clone(),
+        };
+        write_atomic(&self.path, serde_json::to_string(&file)?.as_bytes())
+    }
+}
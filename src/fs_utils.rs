@@ -0,0 +1,45 @@
+use crate::time_utils::now;
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Writes to a temporary file next to the target and renames it over the target,
+/// so that a crash in the middle of the write never leaves a truncated file behind
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Could not create `{}`", dir.display()))?;
+    }
+    let tmp_path = sibling_path(path, "tmp");
+    let mut file = std::fs::File::create(&tmp_path)
+        .with_context(|| format!("Could not create `{}`", tmp_path.display()))?;
+    file.write_all(content)
+        .and_then(|_| file.sync_all())
+        .with_context(|| format!("Could not write `{}`", tmp_path.display()))?;
+    std::fs::rename(&tmp_path, path)
+        .with_context(|| format!("Could not write `{}`", path.display()))
+}
+
+/// Renames a file that could not be parsed so that it can be inspected later
+/// while the caller starts afresh, returns where the file was moved. The name is stamped
+/// with the time, and a counter if needed, so that an earlier backup is never overwritten
+pub fn move_aside(path: &Path) -> Result<PathBuf> {
+    let stamp = now();
+    let corrupt_path = (0..)
+        .map(|n| match n {
+            0 => sibling_path(path, &format!("corrupt.{}", stamp)),
+            n => sibling_path(path, &format!("corrupt.{}.{}", stamp, n)),
+        })
+        .find(|candidate| !candidate.exists())
+        .expect("the counter is unbounded");
+    std::fs::rename(path, &corrupt_path)
+        .with_context(|| format!("Could not move `{}` aside", path.display()))?;
+    Ok(corrupt_path)
+}
+
+fn sibling_path(path: &Path, extension: &str) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".");
+    file_name.push(extension);
+    path.with_file_name(file_name)
+}
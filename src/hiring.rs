@@ -0,0 +1,111 @@
+use crate::csv_utils::csv_field;
+use crate::html_utils::{decode_html, extract_links};
+use crate::HNCLIItem;
+use serde::Serialize;
+
+const HIRING_THREAD_TITLE: &str = "Ask HN: Who is hiring?";
+pub const HIRING_THREAD_AUTHOR: &str = "whoishiring";
+
+/// A job offer extracted from a top level comment of a "Who is hiring?" thread.
+/// Offers are expected to start with a header line like `Company | Role | Location | REMOTE`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JobListing {
+    pub id: i64,
+    pub author: String,
+    pub company: String,
+    pub location: Option<String>,
+    pub remote: bool,
+    pub onsite: bool,
+    pub visa: bool,
+    pub link: Option<String>,
+    pub header: String,
+}
+
+impl std::fmt::Display for JobListing {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut details = vec![self.company.to_string()];
+        if let Some(location) = &self.location {
+            details.push(location.to_string());
+        }
+        if self.remote {
+            details.push("REMOTE".to_string());
+        }
+        if self.onsite {
+            details.push("ONSITE".to_string());
+        }
+        if self.visa {
+            details.push("VISA".to_string());
+        }
+        let link = self.link.as_deref().unwrap_or("no link");
+        write!(f, "{}\n-> {}", details.join(" | "), link)
+    }
+}
+
+pub fn is_hiring_thread(title: &str) -> bool {
+    title.starts_with(HIRING_THREAD_TITLE)
+}
+
+pub fn parse_listing(id: i64, author: &str, html: &str) -> Option<JobListing> {
+    let text = decode_html(html);
+    let header = text.lines().find(|line| !line.trim().is_empty())?.trim();
+    let lowercase_header = header.to_lowercase();
+    let fields: Vec<&str> = header.split('|').map(str::trim).collect();
+    // offers are pipe separated, anything else is a reply or a meta comment
+    if fields.len() < 2 || fields[0].is_empty() {
+        return None;
+    }
+    let location = fields[1..]
+        .iter()
+        .find(|field| looks_like_location(field))
+        .map(|field| field.to_string());
+    Some(JobListing {
+        id,
+        author: author.to_string(),
+        company: fields[0].to_string(),
+        location,
+        remote: lowercase_header.contains("remote"),
+        onsite: ["onsite", "on-site", "on site"]
+            .iter()
+            .any(|keyword| lowercase_header.contains(keyword)),
+        // sponsorship is usually detailed in the body rather than the header
+        visa: text.to_lowercase().contains("visa"),
+        link: extract_links(html).into_iter().next(),
+        header: header.to_string(),
+    })
+}
+
+fn looks_like_location(field: &str) -> bool {
+    let lowercase = field.to_lowercase();
+    let is_link = lowercase.contains("http") || lowercase.contains("www.");
+    let is_salary = field.contains(['$', '€', '£']);
+    !is_link
+        && !is_salary
+        && (field.contains(',')
+            || ["remote", "onsite", "on-site", "hybrid"]
+                .iter()
+                .any(|keyword| lowercase.contains(keyword)))
+}
+
+pub fn listings_to_csv(listings: &[JobListing]) -> String {
+    let mut csv = String::from("id,author,company,location,remote,onsite,visa,link\n");
+    for listing in listings {
+        let row = [
+            listing.id.to_string(),
+            csv_field(&listing.author),
+            csv_field(&listing.company),
+            csv_field(listing.location.as_deref().unwrap_or("")),
+            listing.remote.to_string(),
+            listing.onsite.to_string(),
+            listing.visa.to_string(),
+            csv_field(listing.link.as_deref().unwrap_or("")),
+        ];
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+pub struct HiringThread {
+    pub story: HNCLIItem,
+    pub listings: Vec<JobListing>,
+}
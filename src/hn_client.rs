@@ -1,83 +1,216 @@
-use anyhow::{Context, Result};
+use crate::rate_limit::RateLimiter;
+use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
 use futures::future::join_all;
 use mockall::automock;
 use reqwest::header::USER_AGENT;
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use reqwest::{Client, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
 
 const HN_API_URL: &str = "https://hacker-news.firebaseio.com/";
 const YC_URL: &str = "https://news.ycombinator.com/";
+/// The number of items on a page of the website
+pub const YC_PAGE_SIZE: usize = 30;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// An item of the API, the fields that are missing or of an unexpected type taking their
+/// default value so that an odd item does not fail a whole listing
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct HackerNewsItem {
+    #[serde(default, deserialize_with = "lenient")]
     pub by: String,
-    pub score: i32,
+    #[serde(default, deserialize_with = "lenient")]
+    pub score: Option<i32>,
+    #[serde(default, deserialize_with = "lenient")]
     pub time: u64,
+    #[serde(default, deserialize_with = "lenient")]
     pub title: String,
+    #[serde(default, deserialize_with = "lenient")]
     pub url: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub text: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub parts: Option<Vec<i64>>,
+    /// The comment or story replied to, only set on comments
+    #[serde(default, deserialize_with = "lenient")]
+    pub parent: Option<i64>,
+    /// The poll of a poll option
+    #[serde(default, deserialize_with = "lenient")]
+    pub poll: Option<i64>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub deleted: bool,
+    #[serde(default, deserialize_with = "lenient")]
+    pub dead: bool,
+    #[serde(default, deserialize_with = "lenient")]
     pub descendants: Option<i32>,
-    pub(crate) id: i32,
-    pub(crate) kids: Option<Vec<i32>>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub(crate) id: i64,
+    #[serde(default, deserialize_with = "lenient")]
+    pub(crate) kids: Option<Vec<i64>>,
+    #[serde(default, deserialize_with = "lenient")]
     pub(crate) r#type: String,
 }
 
+impl HackerNewsItem {
+    /// Stands for an item the API returned as `null`, shown like a deleted one
+    pub fn placeholder(id: i64) -> Self {
+        Self {
+            id,
+            deleted: true,
+            ..Self::default()
+        }
+    }
+
+    /// Turns the response for `id` into an item, whatever its shape
+    pub fn recover(id: i64, value: serde_json::Value) -> Self {
+        if !value.is_object() {
+            return Self::placeholder(id);
+        }
+        match serde_json::from_value::<Self>(value) {
+            Ok(item) => Self { id, ..item },
+            Err(_) => Self::placeholder(id),
+        }
+    }
+}
+
+/// Falls back to the default value of a field when its value has an unexpected type
+fn lenient<'de, D, T>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned + Default,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Ok(T::deserialize(value).unwrap_or_default())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HackerNewsUser {
+    pub id: String,
+    pub karma: i32,
+    pub created: u64,
+    pub submitted: Option<Vec<i64>>,
+}
+
 #[automock]
 #[async_trait]
 pub trait HackerNewsClient {
-    async fn get_story_ids(&self, story_type: &str) -> Result<Vec<i32>>;
-    async fn get_items(&self, ids: &[i32]) -> Vec<Result<HackerNewsItem>>;
+    async fn get_story_ids(&self, story_type: &str) -> Result<Vec<i64>>;
+    async fn get_items(&self, ids: &[i64]) -> Vec<Result<HackerNewsItem>>;
+    async fn get_user(&self, username: &str) -> Result<HackerNewsUser>;
+    /// The ids of a page of the public favorites of a user, only listed on the website
+    async fn get_favorite_ids(&self, username: &str, page: u8) -> Result<Vec<i64>>;
+    /// The item exactly as returned by the API, including the fields not modeled here
+    async fn get_raw_item(&self, id: i64) -> Result<serde_json::Value>;
     fn get_y_combinator_url(&self) -> &str;
 }
 
-#[derive(Default)]
 pub struct HackerNewsClientImpl {
     client: Client,
+    limiter: RateLimiter,
+    api_url: String,
+}
+
+impl Default for HackerNewsClientImpl {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[async_trait]
 impl HackerNewsClient for HackerNewsClientImpl {
-    async fn get_story_ids(&self, story_type: &str) -> Result<Vec<i32>> {
-        let url = format!("{}/v0/{}stories.json", HN_API_URL, story_type);
-        let resp = self
-            .client
-            .get(&url)
-            .header(USER_AGENT, "reqwest")
-            .send()
-            .await
-            .with_context(|| format!("Could not retrieve data from `{}`", url))?
-            .json::<Vec<i32>>()
-            .await?;
-        Ok(resp)
+    async fn get_story_ids(&self, story_type: &str) -> Result<Vec<i64>> {
+        let url = format!("{}/v0/{}stories.json", self.api_url, story_type);
+        self.get_json(&url).await
     }
 
-    async fn get_items(&self, ids: &[i32]) -> Vec<Result<HackerNewsItem>> {
+    async fn get_items(&self, ids: &[i64]) -> Vec<Result<HackerNewsItem>> {
         let future_items = ids.iter().map(|id| self.get_item(id));
         return join_all(future_items).await;
     }
 
+    async fn get_user(&self, username: &str) -> Result<HackerNewsUser> {
+        check_username(username)?;
+        let url = format!("{}/v0/user/{}.json", self.api_url, username);
+        let resp: Option<HackerNewsUser> = self.get_json(&url).await?;
+        resp.with_context(|| format!("User `{}` does not exist", username))
+    }
+
+    async fn get_raw_item(&self, id: i64) -> Result<serde_json::Value> {
+        let url = format!("{}/v0/item/{}.json", self.api_url, id);
+        let resp: Option<serde_json::Value> = self.get_json(&url).await?;
+        resp.with_context(|| format!("Item `{}` does not exist", id))
+    }
+
+    async fn get_favorite_ids(&self, username: &str, page: u8) -> Result<Vec<i64>> {
+        check_username(username)?;
+        let url = format!("{}favorites?id={}&p={}", YC_URL, username, page);
+        let html = self.send(&url).await?.text().await?;
+        Ok(parse_item_row_ids(&html))
+    }
+
     fn get_y_combinator_url(&self) -> &str {
         YC_URL
     }
 }
 
+/// Rejects a username that HN could not have issued, so that it is never spliced
+/// into a URL where it would change the path or the query
+pub(crate) fn check_username(username: &str) -> Result<()> {
+    let valid = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    if username.is_empty() || !username.chars().all(valid) {
+        bail!("Invalid username: {}", username);
+    }
+    Ok(())
+}
+
+/// Extracts the ids of the item rows of a page of the website, e.g. `<tr class="athing" id="42">`
+pub(crate) fn parse_item_row_ids(html: &str) -> Vec<i64> {
+    html.split("<tr class=")
+        .skip(1)
+        .filter(|row| row.get(1..).is_some_and(|row| row.starts_with("athing")))
+        .filter_map(|row| {
+            let id = row.split_once("id=")?.1.trim_start_matches(['\'', '"']);
+            id.split(['\'', '"']).next()?.parse().ok()
+        })
+        .collect()
+}
+
 impl HackerNewsClientImpl {
     pub fn new() -> Self {
+        Self::with_api_url(HN_API_URL)
+    }
+
+    /// Targets another instance of the API, e.g. a mirror or a local mock
+    pub fn with_api_url(api_url: &str) -> Self {
         Self {
             client: Client::new(),
+            limiter: RateLimiter::default(),
+            api_url: api_url.trim_end_matches('/').to_string(),
         }
     }
-    async fn get_item(&self, id: &i32) -> Result<HackerNewsItem> {
-        let url = format!("{}/v0/item/{}.json", HN_API_URL, id);
+
+    async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        Ok(self.send(url).await?.json::<T>().await?)
+    }
+
+    async fn send(&self, url: &str) -> Result<reqwest::Response> {
+        self.limiter.acquire().await;
         let resp = self
             .client
-            .get(&url)
+            .get(url)
             .header(USER_AGENT, "reqwest")
             .send()
             .await
-            .with_context(|| format!("Could not retrieve data from `{}`", url))?
-            .json::<HackerNewsItem>()
-            .await?;
+            .with_context(|| format!("Could not retrieve data from `{}`", url))?;
+        if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+            bail!("Rate limited by `{}`, try again later", url);
+        }
         Ok(resp)
     }
+
+    async fn get_item(&self, id: &i64) -> Result<HackerNewsItem> {
+        let url = format!("{}/v0/item/{}.json", self.api_url, id);
+        let value = self.get_json(&url).await?;
+        Ok(HackerNewsItem::recover(*id, value))
+    }
 }
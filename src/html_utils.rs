@@ -0,0 +1,51 @@
+/// Turns the HTML found in item texts into plain text: paragraphs become line breaks,
+/// tags are dropped and the entities used by the HN API are decoded
+pub fn decode_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        if tag.eq_ignore_ascii_case("p") || tag.to_ascii_lowercase().starts_with("p ") {
+            text.push_str("\n\n");
+        }
+        rest = &rest[start + end + 1..];
+    }
+    text.push_str(rest);
+    decode_entities(&text)
+}
+
+pub fn decode_entities(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&#39;", "'")
+        .replace("&#x2F;", "/")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Shortens text to its first line, cut to at most `max_chars` characters
+pub fn snippet(text: &str, max_chars: usize) -> String {
+    let first_line = text
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("");
+    match first_line.char_indices().nth(max_chars) {
+        Some((idx, _)) => format!("{}...", &first_line[..idx]),
+        None => first_line.to_string(),
+    }
+}
+
+/// Collects the targets of the `<a href="...">` links found in the HTML
+pub fn extract_links(html: &str) -> Vec<String> {
+    html.split("href=\"")
+        .skip(1)
+        .filter_map(|rest| rest.split('"').next())
+        .map(decode_entities)
+        .collect()
+}
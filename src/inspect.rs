@@ -0,0 +1,40 @@
+use serde::Serialize;
+use serde_json::Value;
+
+/// The API record of an item with what can be derived from its neighbours
+#[derive(Debug, Serialize)]
+pub struct ItemInspection {
+    pub item: Value,
+    /// From the direct parent up to the story
+    pub parents: Vec<Value>,
+    /// 0 for a story, 1 for a top level comment and so on
+    pub depth: usize,
+    pub descendants: usize,
+}
+
+/// One line describing a raw item, e.g. `123 comment by pg`
+pub fn summary(item: &Value) -> String {
+    let field = |name: &str| item.get(name).and_then(Value::as_str).unwrap_or_default();
+    let id = item.get("id").and_then(Value::as_i64).unwrap_or_default();
+    let mut summary = format!("{} {} by {}", id, field("type"), field("by"));
+    if !field("title").is_empty() {
+        summary.push_str(&format!(": {}", field("title")));
+    }
+    summary
+}
+
+impl std::fmt::Display for ItemInspection {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{}", summary(&self.item))?;
+        writeln!(
+            f,
+            "depth: {} - descendants: {}",
+            self.depth, self.descendants
+        )?;
+        for (idx, parent) in self.parents.iter().enumerate() {
+            writeln!(f, "{}^ {}", "  ".repeat(idx), summary(parent))?;
+        }
+        let record = serde_json::to_string_pretty(&self.item).map_err(|_| std::fmt::Error)?;
+        write!(f, "\n{}", record)
+    }
+}
@@ -1,42 +1,322 @@
-use crate::hn_client::{HackerNewsClient, HackerNewsClientImpl, HackerNewsItem};
-use crate::time_utils::{time_ago, unix_epoch_to_datetime};
-use anyhow::Result;
+use crate::hiring::{is_hiring_thread, parse_listing, HIRING_THREAD_AUTHOR};
+use crate::hn_client::{HackerNewsClient, HackerNewsItem, YC_PAGE_SIZE};
+use crate::html_utils::{decode_html, snippet};
+use crate::search_client::{SearchClient, SearchClientImpl, SearchHit};
+use crate::theme::paint;
+use crate::time_utils::{age_bucket, now, time_ago, unix_epoch_to_datetime};
+use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
-use std::collections::HashSet;
+use futures::future::{join_all, BoxFuture};
+use futures::stream::FuturesOrdered;
+use futures::{FutureExt, StreamExt};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 
+pub use crate::activity::{ActivityEvent, ActivityLog};
+pub use crate::bookmarks::{Bookmark, Bookmarks};
+pub use crate::comment_body::{Block, CommentBody, Span, SpanStyle};
+pub use crate::comments::{
+    filter_by_author, grep_comments, prune_deleted, CommentWindow, Crumb, HNCLIComment,
+    HNCLICommentRecord, HNCLIThread, ThreadEntry, ThreadLink,
+};
+pub use crate::config::{CollapseRules, Config};
+pub use crate::csv_utils::items_to_csv;
+pub use crate::flair::{FlairCache, UserFlair};
+pub use crate::hiring::{listings_to_csv, HiringThread, JobListing};
+pub use crate::hn_client::HackerNewsClientImpl;
+pub use crate::inspect::ItemInspection;
+pub use crate::paths::Paths;
+pub use crate::progress::ReadingProgress;
+pub use crate::seen_ids::SeenIds;
+pub use crate::theme::{Theme, Themed, BUILTIN_THEMES};
+pub use crate::topics::{extract_topics, topic_terms, Topic};
+
+mod activity;
+mod bookmarks;
+mod comment_body;
+mod comments;
+mod config;
+mod csv_utils;
+mod flair;
+mod fs_utils;
+mod hiring;
 mod hn_client;
+mod html_utils;
+mod inspect;
+mod paths;
+mod progress;
+mod rate_limit;
+mod schema;
+mod search_client;
+mod seen_ids;
+mod theme;
 mod time_utils;
+mod topics;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct HNCLIItem {
+    pub id: i64,
     pub title: String,
     pub url: String,
     pub author: String,
     pub time: String,
     pub time_ago: String,
-    pub score: i32,
+    pub timestamp: u64,
+    pub score: Option<i32>,
     pub comments: Option<i32>,
+    pub is_job: bool,
+    pub is_launch: bool,
+    pub poll_options: Vec<PollOption>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PollOption {
+    pub text: String,
+    pub score: i32,
+}
+
+impl HNCLIItem {
+    /// Coarse age of the item, used to group stories of the same period together
+    pub fn age_bucket(&self) -> String {
+        age_bucket(self.timestamp)
+    }
+
+    /// The points earned per hour since the item was posted, the first hour counting as a
+    /// whole one so that the first votes of a fresh story do not make it jump ahead
+    pub fn velocity(&self, now: u64) -> f64 {
+        let hours = now.saturating_sub(self.timestamp) as f64 / 3600.0;
+        self.score.unwrap_or_default() as f64 / hours.max(1.0)
+    }
+
+    /// The host of the URL without its "www." prefix, e.g. "arxiv.org"
+    pub fn domain(&self) -> &str {
+        let url = self
+            .url
+            .split_once("://")
+            .map_or(&*self.url, |(_, rest)| rest);
+        let host = url.split(['/', '?', '#']).next().unwrap_or_default();
+        host.strip_prefix("www.").unwrap_or(host)
+    }
+}
+
+/// Groups the positions of the items by domain, the groups and the items within them
+/// keeping the order of the list
+pub fn group_by_domain<'a>(items: &[&'a HNCLIItem]) -> Vec<(&'a str, Vec<usize>)> {
+    let mut groups: Vec<(&str, Vec<usize>)> = Vec::new();
+    for (idx, item) in items.iter().enumerate() {
+        let domain = item.domain();
+        match groups.iter_mut().find(|(group, _)| *group == domain) {
+            Some((_, positions)) => positions.push(idx),
+            None => groups.push((domain, vec![idx])),
+        }
+    }
+    groups
 }
 
 impl std::fmt::Display for HNCLIItem {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let comment_str = match self.comments {
-            Some(comments) => format!("{} comments", comments),
-            None => String::new(),
-        };
-        let first_line = format!("{} by {}", self.title, self.author);
-        let second_line = format!(
-            "[{} points] - {} - {}",
-            self.score, comment_str, self.time_ago
+        Themed(self, &Theme::plain()).fmt(f)
+    }
+}
+
+impl std::fmt::Display for Themed<'_, HNCLIItem> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let Themed(item, theme) = self;
+        let mut details = Vec::new();
+        if item.is_job {
+            details.push("[job]".to_string());
+        }
+        if item.is_launch {
+            details.push("[launch]".to_string());
+        }
+        if let Some(score) = item.score {
+            details.push(format!("[{} points]", score));
+        }
+        if let Some(comments) = item.comments {
+            details.push(format!("{} comments", comments));
+        }
+        details.push(item.time_ago.to_string());
+        let first_line = format!(
+            "{} by {}",
+            paint(&theme.title, &item.title),
+            paint(&theme.author, &item.author)
         );
-        let last_line = format!("-> {}", self.url);
-        write!(f, "{}\n{}\n{}", first_line, second_line, last_line)
+        let second_line = paint(&theme.details, details.join(" - "));
+        let poll_lines: String = item
+            .poll_options
+            .iter()
+            .map(|option| format!("  o {} ({} points)\n", option.text, option.score))
+            .collect();
+        let last_line = format!("-> {}", paint(&theme.url, &item.url));
+        write!(
+            f,
+            "{}\n{}\n{}{}",
+            first_line, second_line, poll_lines, last_line
+        )
+    }
+}
+
+/// YC companies announce their launch with "Launch HN: <company> (YC <batch>) - <pitch>"
+fn is_launch_title(title: &str) -> bool {
+    title.starts_with("Launch HN:")
+}
+
+fn raw_parent_id(item: &serde_json::Value) -> Option<i64> {
+    item.get("parent")?.as_i64()
+}
+
+/// A story by its title, a comment by its author
+fn raw_crumb(item: &serde_json::Value) -> Crumb {
+    let field = |name: &str| item.get(name).and_then(serde_json::Value::as_str);
+    let label = match (
+        field("title").filter(|title| !title.is_empty()),
+        field("by"),
+    ) {
+        (Some(title), _) => title,
+        (None, Some(author)) => author,
+        (None, None) => "[deleted]",
+    };
+    Crumb {
+        id: item
+            .get("id")
+            .and_then(serde_json::Value::as_i64)
+            .unwrap_or_default(),
+        label: label.to_string(),
     }
 }
 
+/// A streamed comment, blanked like the ones of `fetch_thread` when dead unless `show_dead`
+fn comment_record(
+    parent: i64,
+    depth: u8,
+    mut comment: HNCLIComment,
+    show_dead: bool,
+) -> HNCLICommentRecord {
+    if !show_dead {
+        comment.hide_dead();
+    }
+    HNCLICommentRecord {
+        parent,
+        depth,
+        comment,
+    }
+}
+
+/// The title of a story and its text if any, the "[N]" markers of the links resolved below
+fn plain_text(item: &HackerNewsItem) -> String {
+    let body = CommentBody::parse(item.text.as_deref().unwrap_or_default());
+    let mut parts: Vec<String> = [item.title.clone(), body.to_string()]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect();
+    if !body.links.is_empty() {
+        let links: Vec<String> = (body.links.iter().enumerate())
+            .map(|(idx, link)| format!("[{}] {}", idx + 1, link))
+            .collect();
+        parts.push(links.join("\n"));
+    }
+    parts.join("\n\n")
+}
+
+fn page_ids(ids: &[i64], page: u8, n: u8) -> &[i64] {
+    let start = ids.len().min(page.saturating_sub(1) as usize * n as usize);
+    let end = ids.len().min(start + n as usize);
+    &ids[start..end]
+}
+
 #[async_trait]
 pub trait HackerNewsCliService {
-    async fn fetch_top_n_stories(&self, story_type: &str, n: u8) -> Result<Vec<HNCLIItem>>;
+    /// Fetches the `n` stories of the given page, pages start at 1
+    async fn fetch_top_n_stories(
+        &self,
+        story_type: &str,
+        page: u8,
+        n: u8,
+    ) -> Result<Vec<HNCLIItem>>;
+
+    async fn search_stories(&self, query: &str, page: u8, n: u8) -> Result<Vec<HNCLIItem>>;
+
+    async fn fetch_past_discussions(&self, story_url: &str, n: u8) -> Result<Vec<HNCLIItem>>;
+
+    async fn fetch_user_submissions(
+        &self,
+        username: &str,
+        page: u8,
+        n: u8,
+    ) -> Result<Vec<HNCLIItem>>;
+
+    /// Lists the stories and comments a user marked as favorite, scraped from the website
+    async fn fetch_user_favorites(&self, username: &str, page: u8, n: u8)
+        -> Result<Vec<HNCLIItem>>;
+
+    /// Fetches an item with its comments, down to `depth` levels of replies
+    async fn fetch_thread(&self, id: i64, depth: u8, window: CommentWindow) -> Result<HNCLIThread>;
+
+    /// Fetches an item and all its comments however deep, `on_progress` being given the
+    /// number of comments fetched by each batch of requests
+    async fn fetch_full_thread(
+        &self,
+        id: i64,
+        window: CommentWindow,
+        on_progress: &(dyn Fn(usize) + Send + Sync),
+    ) -> Result<HNCLIThread>;
+
+    /// Same as `fetch_top_n_stories`, but hands over each story as soon as it is fetched
+    async fn stream_top_n_stories(
+        &self,
+        story_type: &str,
+        page: u8,
+        n: u8,
+        on_item: &(dyn Fn(HNCLIItem) + Send + Sync),
+    ) -> Result<()>;
+
+    /// Same as `fetch_thread`, but hands over the story then each comment as soon as it is
+    /// fetched, one level of replies after the other. The text of the dead comments is only
+    /// kept with `show_dead`
+    async fn stream_thread(
+        &self,
+        id: i64,
+        depth: u8,
+        window: CommentWindow,
+        show_dead: bool,
+        on_entry: &(dyn Fn(ThreadEntry) + Send + Sync),
+    ) -> Result<()>;
+
+    /// Finds the earlier submissions of the URL of a story, each one linking to its discussion
+    async fn fetch_previous_discussions(&self, story: &HNCLIItem, n: u8) -> Result<Vec<HNCLIItem>>;
+
+    /// Finds the other submissions of a story, those of the same URL first and then those
+    /// with a similar title
+    async fn fetch_similar_stories(&self, id: i64, n: u8) -> Result<Vec<HNCLIItem>>;
+
+    /// Looks up the karma and account age of users, those that do not exist being left out
+    async fn fetch_user_flairs(&self, usernames: &[&str]) -> HashMap<String, UserFlair>;
+
+    /// Extracts the job listings of a "Who is hiring?" thread, the latest one if no id is given
+    async fn fetch_hiring_thread(&self, thread_id: Option<i64>) -> Result<HiringThread>;
+
+    /// Fetches an item as returned by the API, with all its fields
+    async fn fetch_raw_item(&self, id: i64) -> Result<serde_json::Value>;
+
+    /// Walks the parent pointers of an item up to its story, returning the API records
+    /// of the direct parent first and of the story last
+    async fn resolve_ancestors(&self, item_id: i64) -> Result<Vec<serde_json::Value>>;
+
+    /// Looks up what a bookmark keeps of an item: its author, the start of its text and the
+    /// title of its story
+    async fn fetch_bookmark(&self, id: i64) -> Result<Bookmark>;
+
+    /// Fetches the API record of an item with its parents and counts its descendants
+    async fn inspect_item(&self, id: i64) -> Result<ItemInspection>;
+
+    /// The targets of the links found in the text of an item, numbered from 1 in the output
+    async fn fetch_item_links(&self, id: i64) -> Result<Vec<String>>;
+
+    /// The text of an item without styles, its links listed after it, e.g. to paste it elsewhere
+    async fn fetch_item_text(&self, id: i64) -> Result<String>;
+
+    /// The URL of the HN page of an item, which also works for a comment
+    fn permalink(&self, id: i64) -> String;
 
     fn get_valid_story_types() -> HashSet<&'static str>;
 }
@@ -46,45 +326,421 @@ pub struct HackerNewsCliServiceImpl {
     // https://blog.rust-lang.org/2023/12/21/async-fn-rpit-in-traits.html#dynamic-dispatch
     // TODO replace with Box<dyn HackerNewsClient>
     hn_client: HackerNewsClientImpl,
+    search_client: SearchClientImpl,
 }
 
 #[async_trait]
 impl HackerNewsCliService for HackerNewsCliServiceImpl {
-    async fn fetch_top_n_stories(&self, story_type: &str, n: u8) -> Result<Vec<HNCLIItem>> {
+    async fn fetch_top_n_stories(
+        &self,
+        story_type: &str,
+        page: u8,
+        n: u8,
+    ) -> Result<Vec<HNCLIItem>> {
         let ids = self
             .hn_client
             .get_story_ids(story_type)
             .await
-            .unwrap_or_else(|_| panic!("Failed to get ids from story type {}", story_type));
+            .with_context(|| format!("Failed to get ids from story type {}", story_type))?;
 
-        // fetches a lot of ids by default, limit that by length given in args
-        let ids = &ids[..n as usize];
-        Ok(self
+        // fetches a lot of ids by default, limit that to the requested page
+        let ids = page_ids(&ids, page, n);
+        let items = self
             .hn_client
             .get_items(ids)
             .await
             .into_iter()
-            .map(|x| self.api_item_to_hn_cli_item(x.unwrap()))
+            .collect::<Result<_>>()?;
+        Ok(self.api_items_to_hn_cli_items(items).await)
+    }
+
+    async fn search_stories(&self, query: &str, page: u8, n: u8) -> Result<Vec<HNCLIItem>> {
+        Ok(self
+            .search_client
+            .search_stories(query, page, n)
+            .await?
+            .into_iter()
+            .map(|hit| self.search_hit_to_hn_cli_item(hit))
+            .collect())
+    }
+
+    async fn fetch_past_discussions(&self, story_url: &str, n: u8) -> Result<Vec<HNCLIItem>> {
+        Ok(self
+            .search_client
+            .search_stories_by_url(story_url, n)
+            .await?
+            .into_iter()
+            .map(|hit| self.search_hit_to_hn_cli_item(hit))
+            .collect())
+    }
+
+    async fn fetch_previous_discussions(&self, story: &HNCLIItem, n: u8) -> Result<Vec<HNCLIItem>> {
+        let yc_url = self.hn_client.get_y_combinator_url();
+        // self posts only have their own discussion as URL
+        if story.url.starts_with(yc_url) {
+            return Ok(Vec::new());
+        }
+        Ok(self
+            .search_client
+            .search_stories_by_url(&story.url, n)
+            .await?
+            .into_iter()
+            .map(|hit| self.search_hit_to_hn_cli_item(hit))
+            .filter(|item| item.id != story.id && item.timestamp < story.timestamp)
+            .map(|item| HNCLIItem {
+                url: format!("{}item?id={}", yc_url, item.id),
+                ..item
+            })
             .collect())
     }
 
+    async fn fetch_similar_stories(&self, id: i64, n: u8) -> Result<Vec<HNCLIItem>> {
+        let item = self.get_item(id).await?;
+        let mut hits = match &item.url {
+            Some(url) => self.search_client.search_stories_by_url(url, n).await?,
+            None => Vec::new(),
+        };
+        hits.extend(self.search_client.search_stories(&item.title, 1, n).await?);
+        let mut items: Vec<HNCLIItem> = Vec::new();
+        for hit in hits {
+            let similar = self.search_hit_to_hn_cli_item(hit);
+            if similar.id != id && !items.iter().any(|item| item.id == similar.id) {
+                items.push(similar);
+            }
+        }
+        items.truncate(n.into());
+        Ok(items)
+    }
+
+    async fn fetch_user_submissions(
+        &self,
+        username: &str,
+        page: u8,
+        n: u8,
+    ) -> Result<Vec<HNCLIItem>> {
+        let user = self.hn_client.get_user(username).await?;
+        let ids = user.submitted.unwrap_or_default();
+        let ids = page_ids(&ids, page, n);
+        // deleted submissions are returned without most of their fields, skip them
+        let items = self
+            .hn_client
+            .get_items(ids)
+            .await
+            .into_iter()
+            .filter_map(|item| item.ok())
+            .filter(|item| !item.deleted && !item.dead)
+            .collect();
+        Ok(self.api_items_to_hn_cli_items(items).await)
+    }
+
+    async fn fetch_user_favorites(
+        &self,
+        username: &str,
+        page: u8,
+        n: u8,
+    ) -> Result<Vec<HNCLIItem>> {
+        let wanted = usize::from(page) * usize::from(n);
+        let mut ids = Vec::new();
+        // the website pages do not match ours, read them until ours is filled
+        for website_page in 1..=u8::MAX {
+            let favorite_ids = self
+                .hn_client
+                .get_favorite_ids(username, website_page)
+                .await?;
+            let last_page = favorite_ids.len() < YC_PAGE_SIZE;
+            ids.extend(favorite_ids);
+            if last_page || ids.len() >= wanted {
+                break;
+            }
+        }
+        let items = self
+            .hn_client
+            .get_items(page_ids(&ids, page, n))
+            .await
+            .into_iter()
+            .filter_map(|item| item.ok())
+            .filter(|item| !item.deleted && !item.dead)
+            .collect();
+        Ok(self.api_items_to_hn_cli_items(items).await)
+    }
+
+    async fn fetch_user_flairs(&self, usernames: &[&str]) -> HashMap<String, UserFlair> {
+        let users = join_all(
+            usernames
+                .iter()
+                .map(|username| self.hn_client.get_user(username)),
+        )
+        .await;
+        users
+            .into_iter()
+            .filter_map(|user| user.ok())
+            .map(|user| {
+                let flair = UserFlair {
+                    karma: user.karma,
+                    created: user.created,
+                };
+                (user.id, flair)
+            })
+            .collect()
+    }
+
+    async fn fetch_thread(&self, id: i64, depth: u8, window: CommentWindow) -> Result<HNCLIThread> {
+        self.build_thread(id, depth, window, &|_| {}).await
+    }
+
+    async fn fetch_full_thread(
+        &self,
+        id: i64,
+        window: CommentWindow,
+        on_progress: &(dyn Fn(usize) + Send + Sync),
+    ) -> Result<HNCLIThread> {
+        self.build_thread(id, u8::MAX, window, on_progress).await
+    }
+
+    async fn stream_top_n_stories(
+        &self,
+        story_type: &str,
+        page: u8,
+        n: u8,
+        on_item: &(dyn Fn(HNCLIItem) + Send + Sync),
+    ) -> Result<()> {
+        let ids = self.hn_client.get_story_ids(story_type).await?;
+        let ids = page_ids(&ids, page, n);
+        let mut items: FuturesOrdered<_> = ids.iter().map(|id| self.get_item(*id)).collect();
+        while let Some(item) = items.next().await {
+            on_item(self.api_item_to_hn_cli_item_with_poll(item?).await);
+        }
+        Ok(())
+    }
+
+    async fn stream_thread(
+        &self,
+        id: i64,
+        depth: u8,
+        window: CommentWindow,
+        show_dead: bool,
+        on_entry: &(dyn Fn(ThreadEntry) + Send + Sync),
+    ) -> Result<()> {
+        let item = self.get_item(id).await?;
+        let comment_ids = item.kids.clone().unwrap_or_default();
+        let window = window.resolve(&comment_ids);
+        let mut level = vec![(item.id, comment_ids)];
+        on_entry(ThreadEntry::Story(
+            self.api_item_to_hn_cli_item_with_poll(item).await,
+        ));
+        for current_depth in 1..=depth {
+            if level.is_empty() {
+                break;
+            }
+            let mut next_level = Vec::new();
+            let mut comments: FuturesOrdered<_> = std::mem::take(&mut level)
+                .into_iter()
+                .flat_map(|(parent, ids)| {
+                    let ids = window.apply(&ids, current_depth == 1).to_vec();
+                    ids.into_iter()
+                        .map(move |id| async move { (parent, self.get_item(id).await) })
+                })
+                .collect();
+            while let Some((parent, comment)) = comments.next().await {
+                let Ok(comment) = comment else {
+                    continue;
+                };
+                next_level.push((comment.id, comment.kids.clone().unwrap_or_default()));
+                let comment = self.api_item_to_hn_cli_comment(comment, Vec::new());
+                on_entry(ThreadEntry::Comment(comment_record(
+                    parent,
+                    current_depth,
+                    comment,
+                    show_dead,
+                )));
+            }
+            level = next_level;
+        }
+        Ok(())
+    }
+
+    async fn fetch_hiring_thread(&self, thread_id: Option<i64>) -> Result<HiringThread> {
+        let thread = match thread_id {
+            Some(id) => self.get_item(id).await?,
+            None => self.find_latest_hiring_thread().await?,
+        };
+        let comment_ids = thread.kids.clone().unwrap_or_default();
+        let listings = self
+            .hn_client
+            .get_items(&comment_ids)
+            .await
+            .into_iter()
+            .filter_map(|comment| comment.ok())
+            .filter_map(|comment| parse_listing(comment.id, &comment.by, comment.text.as_deref()?))
+            .collect();
+        Ok(HiringThread {
+            story: self.api_item_to_hn_cli_item(thread),
+            listings,
+        })
+    }
+
+    async fn fetch_raw_item(&self, id: i64) -> Result<serde_json::Value> {
+        self.hn_client.get_raw_item(id).await
+    }
+
+    async fn fetch_item_links(&self, id: i64) -> Result<Vec<String>> {
+        let item = self.get_item(id).await?;
+        Ok(CommentBody::parse(item.text.as_deref().unwrap_or_default()).links)
+    }
+
+    async fn fetch_item_text(&self, id: i64) -> Result<String> {
+        Ok(plain_text(&self.get_item(id).await?))
+    }
+
+    fn permalink(&self, id: i64) -> String {
+        format!("{}item?id={}", self.hn_client.get_y_combinator_url(), id)
+    }
+
+    async fn resolve_ancestors(&self, item_id: i64) -> Result<Vec<serde_json::Value>> {
+        let parent_id = raw_parent_id(&self.hn_client.get_raw_item(item_id).await?);
+        self.fetch_ancestors(parent_id).await
+    }
+
+    async fn fetch_bookmark(&self, id: i64) -> Result<Bookmark> {
+        let item = self.hn_client.get_raw_item(id).await?;
+        if item.is_null() {
+            bail!("Item {} does not exist", id);
+        }
+        let ancestors = self.fetch_ancestors(raw_parent_id(&item)).await?;
+        let story = ancestors.last().unwrap_or(&item);
+        let field = |item: &serde_json::Value, name: &str| {
+            let value = item.get(name).and_then(serde_json::Value::as_str);
+            value.unwrap_or_default().to_string()
+        };
+        let text = match field(&item, "text") {
+            text if text.is_empty() => field(&item, "title"),
+            text => text,
+        };
+        Ok(Bookmark {
+            item: id,
+            story: story
+                .get("id")
+                .and_then(serde_json::Value::as_i64)
+                .unwrap_or(id),
+            title: field(story, "title"),
+            author: field(&item, "by"),
+            snippet: snippet(&decode_html(&text), 80),
+            url: self.permalink(id),
+            saved_at: now(),
+        })
+    }
+
+    async fn inspect_item(&self, id: i64) -> Result<ItemInspection> {
+        let item = self.hn_client.get_raw_item(id).await?;
+        // walked from the item already fetched rather than fetching it again
+        let parents = self.fetch_ancestors(raw_parent_id(&item)).await?;
+        // stories carry their count, comments have to be walked
+        let descendants = match item.get("descendants").and_then(serde_json::Value::as_u64) {
+            Some(descendants) => descendants as usize,
+            None => {
+                let kids: Vec<i64> = item
+                    .get("kids")
+                    .and_then(|kids| serde_json::from_value(kids.clone()).ok())
+                    .unwrap_or_default();
+                self.count_descendants(&kids).await
+            }
+        };
+        Ok(ItemInspection {
+            item,
+            depth: parents.len(),
+            parents,
+            descendants,
+        })
+    }
+
     fn get_valid_story_types() -> HashSet<&'static str> {
-        HashSet::from(["best", "new", "top"])
+        HashSet::from(["ask", "best", "job", "new", "show", "top"])
     }
 }
 
 impl HackerNewsCliServiceImpl {
+    /// Fetches an item and `depth` levels of comments, `on_progress` being given the number
+    /// of comments fetched by each request batch
+    async fn build_thread(
+        &self,
+        id: i64,
+        depth: u8,
+        window: CommentWindow,
+        on_progress: &(dyn Fn(usize) + Send + Sync),
+    ) -> Result<HNCLIThread> {
+        let item = self.get_item(id).await?;
+        let comment_ids = item.kids.clone().unwrap_or_default();
+        let window = window.resolve(&comment_ids);
+        let comments = self
+            .fetch_comments(window.apply(&comment_ids, true), depth, window, on_progress)
+            .await;
+        let text = item
+            .text
+            .as_deref()
+            .filter(|text| !text.is_empty())
+            .map(CommentBody::parse);
+        // opened at a comment, the way up to the story is shown above it
+        let breadcrumb = self
+            .fetch_ancestors(item.parent)
+            .await?
+            .iter()
+            .rev()
+            .map(raw_crumb)
+            .collect();
+        let story = self.api_item_to_hn_cli_item_with_poll(item).await;
+        Ok(HNCLIThread {
+            story,
+            breadcrumb,
+            text,
+            previous: Vec::new(),
+            flairs: HashMap::new(),
+            window,
+            comment_count: comment_ids.len(),
+            flat: false,
+            new_since: None,
+            resumed: false,
+            comments,
+        })
+    }
+
     pub fn new(client: Option<HackerNewsClientImpl>) -> Self {
         match client {
             None => HackerNewsCliServiceImpl {
                 hn_client: HackerNewsClientImpl::new(),
+                search_client: SearchClientImpl::new(),
+            },
+            Some(hn_client) => HackerNewsCliServiceImpl {
+                hn_client,
+                search_client: SearchClientImpl::new(),
             },
-            Some(hn_client) => HackerNewsCliServiceImpl { hn_client },
         }
     }
 }
 
 impl HackerNewsCliServiceImpl {
+    async fn get_item(&self, id: i64) -> Result<HackerNewsItem> {
+        self.hn_client
+            .get_items(&[id])
+            .await
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| Err(anyhow!("Item {} not found", id)))
+    }
+
+    async fn find_latest_hiring_thread(&self) -> Result<HackerNewsItem> {
+        // the account posts the hiring, freelancer and hired threads together each month
+        let user = self.hn_client.get_user(HIRING_THREAD_AUTHOR).await?;
+        let ids = user.submitted.unwrap_or_default();
+        let ids = &ids[..ids.len().min(6)];
+        self.hn_client
+            .get_items(ids)
+            .await
+            .into_iter()
+            .filter_map(|item| item.ok())
+            .find(|item| is_hiring_thread(&item.title))
+            .ok_or_else(|| anyhow!("Could not find a recent \"Who is hiring?\" thread"))
+    }
+
     fn get_item_url(&self, item: &HackerNewsItem) -> String {
         match &item.url {
             Some(url) => url.to_string(),
@@ -96,15 +752,175 @@ impl HackerNewsCliServiceImpl {
         }
     }
 
+    fn get_item_title(&self, item: &HackerNewsItem) -> String {
+        match (&item.title, &item.text) {
+            (title, Some(text)) if title.is_empty() => {
+                format!("[{}] {}", item.r#type, snippet(&decode_html(text), 80))
+            }
+            (title, None) if title.is_empty() && item.deleted => "[deleted]".to_string(),
+            (title, _) => title.to_string(),
+        }
+    }
+
     fn api_item_to_hn_cli_item(&self, item: HackerNewsItem) -> HNCLIItem {
         HNCLIItem {
-            title: item.title.to_string(),
+            id: item.id,
+            title: self.get_item_title(&item),
             url: self.get_item_url(&item),
             author: item.by,
             time: unix_epoch_to_datetime(item.time),
             time_ago: time_ago(item.time),
+            timestamp: item.time,
             score: item.score,
             comments: item.descendants,
+            is_job: item.r#type == "job",
+            is_launch: is_launch_title(&item.title),
+            poll_options: Vec::new(),
+        }
+    }
+
+    async fn fetch_poll_options(&self, item: &HackerNewsItem) -> Vec<PollOption> {
+        let parts = match &item.parts {
+            Some(parts) if item.r#type == "poll" => parts,
+            _ => return Vec::new(),
+        };
+        self.hn_client
+            .get_items(parts)
+            .await
+            .into_iter()
+            .filter_map(|option| option.ok())
+            .map(|option| PollOption {
+                text: decode_html(option.text.as_deref().unwrap_or_default()),
+                score: option.score.unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    fn fetch_comments<'a>(
+        &'a self,
+        ids: &'a [i64],
+        depth: u8,
+        window: CommentWindow,
+        on_progress: &'a (dyn Fn(usize) + Send + Sync),
+    ) -> BoxFuture<'a, Vec<HNCLIComment>> {
+        async move {
+            if depth == 0 {
+                return Vec::new();
+            }
+            let items: Vec<HackerNewsItem> = self
+                .hn_client
+                .get_items(ids)
+                .await
+                .into_iter()
+                .filter_map(|item| item.ok())
+                .collect();
+            on_progress(items.len());
+            let replies = join_all(items.iter().map(|item| {
+                let reply_ids = window.apply(item.kids.as_deref().unwrap_or_default(), false);
+                self.fetch_comments(reply_ids, depth - 1, window, on_progress)
+            }))
+            .await;
+            items
+                .into_iter()
+                .zip(replies)
+                .map(|(item, replies)| self.api_item_to_hn_cli_comment(item, replies))
+                .collect()
+        }
+        .boxed()
+    }
+
+    /// The raw items from the given parent up to the story
+    async fn fetch_ancestors(&self, parent_id: Option<i64>) -> Result<Vec<serde_json::Value>> {
+        let mut ancestors = Vec::new();
+        let mut parent_id = parent_id;
+        while let Some(id) = parent_id {
+            let parent = self.hn_client.get_raw_item(id).await?;
+            parent_id = raw_parent_id(&parent);
+            ancestors.push(parent);
+        }
+        Ok(ancestors)
+    }
+
+    fn count_descendants<'a>(&'a self, ids: &'a [i64]) -> BoxFuture<'a, usize> {
+        async move {
+            let items: Vec<HackerNewsItem> = self
+                .hn_client
+                .get_items(ids)
+                .await
+                .into_iter()
+                .filter_map(|item| item.ok())
+                .collect();
+            let counts = join_all(
+                items
+                    .iter()
+                    .map(|item| self.count_descendants(item.kids.as_deref().unwrap_or_default())),
+            )
+            .await;
+            ids.len() + counts.into_iter().sum::<usize>()
+        }
+        .boxed()
+    }
+
+    fn api_item_to_hn_cli_comment(
+        &self,
+        item: HackerNewsItem,
+        replies: Vec<HNCLIComment>,
+    ) -> HNCLIComment {
+        HNCLIComment {
+            id: item.id,
+            author: item.by,
+            body: CommentBody::parse(item.text.as_deref().unwrap_or_default()),
+            time_ago: time_ago(item.time),
+            timestamp: item.time,
+            deleted: item.deleted,
+            dead: item.dead,
+            collapsed: false,
+            reply_count: item.kids.map_or(0, |kids| kids.len()),
+            replies,
+        }
+    }
+
+    async fn api_item_to_hn_cli_item_with_poll(&self, item: HackerNewsItem) -> HNCLIItem {
+        let poll_options = self.fetch_poll_options(&item).await;
+        HNCLIItem {
+            poll_options,
+            ..self.api_item_to_hn_cli_item(item)
+        }
+    }
+
+    async fn api_items_to_hn_cli_items(&self, items: Vec<HackerNewsItem>) -> Vec<HNCLIItem> {
+        join_all(
+            items
+                .into_iter()
+                .map(|item| self.api_item_to_hn_cli_item_with_poll(item)),
+        )
+        .await
+    }
+
+    fn search_hit_to_hn_cli_item(&self, hit: SearchHit) -> HNCLIItem {
+        let url = match hit.url {
+            Some(url) if !url.is_empty() => url,
+            _ => format!(
+                "{}item?id={}",
+                self.hn_client.get_y_combinator_url(),
+                hit.object_id
+            ),
+        };
+        let title = hit.title.unwrap_or_default();
+        let is_launch = is_launch_title(&title);
+        HNCLIItem {
+            id: hit.object_id.parse().unwrap_or_default(),
+            title,
+            url,
+            author: hit.author,
+            time: unix_epoch_to_datetime(hit.created_at_i),
+            time_ago: time_ago(hit.created_at_i),
+            timestamp: hit.created_at_i,
+            score: hit.points,
+            comments: hit.num_comments,
+            is_job: false,
+            is_launch,
+            poll_options: Vec::new(),
         }
     }
 }
@@ -116,6 +932,308 @@ mod tests {
     use crate::time_utils::now;
     use mockall::predicate;
 
+    #[test]
+    fn test_resolve_paths_overrides() {
+        let paths = Paths::resolve(None, Some("/etc/hn".into()), Some("/tmp/hn".into())).unwrap();
+        assert_eq!(
+            paths.config_file,
+            std::path::Path::new("/etc/hn/config.toml")
+        );
+        assert_eq!(paths.cache_dir, std::path::Path::new("/tmp/hn"));
+
+        let paths =
+            Paths::resolve(Some("/srv/hn.toml".into()), Some("/etc/hn".into()), None).unwrap();
+        assert_eq!(paths.config_file, std::path::Path::new("/srv/hn.toml"));
+        assert_eq!(
+            paths.theme_file(),
+            std::path::Path::new("/etc/hn/theme.toml")
+        );
+    }
+
+    #[test]
+    fn test_xdg_app_dir() {
+        let home = std::path::Path::new("/home/me");
+        assert_eq!(
+            paths::xdg_app_dir(None, home, ".config"),
+            std::path::Path::new("/home/me/.config/hn-cli")
+        );
+        assert_eq!(
+            paths::xdg_app_dir(Some("/xdg/config".into()), home, ".config"),
+            std::path::Path::new("/xdg/config/hn-cli")
+        );
+        assert_eq!(
+            paths::xdg_app_dir(Some("relative/config".into()), home, ".config"),
+            std::path::Path::new("/home/me/.config/hn-cli")
+        );
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse("story_type = \"top\"\npage_size = 30\n").unwrap();
+        assert_eq!(config.story_type.as_deref(), Some("top"));
+        assert_eq!(config.page_size, Some(30));
+        assert_eq!(config.comment_depth, None);
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert!(Config::parse("page_size = 0").is_err());
+        assert!(Config::parse("unknown = 1").is_err());
+        assert!(Config::parse("theme = \"gruvbox\"").is_ok());
+        let config = Config::parse("auto_expand_depth = 2").unwrap();
+        assert_eq!(config.comment_depth, Some(2));
+        assert!(Config::parse("theme = \"unknown\"").is_err());
+
+        let env = |var: &str| match var {
+            "HN_CLI_PAGE_SIZE" => Some("20".to_string()),
+            "HN_CLI_API_URL" => Some("http://localhost:8080".to_string()),
+            _ => None,
+        };
+        let mut config = Config::parse("story_type = \"top\"\npage_size = 30\n").unwrap();
+        config.apply_env(env).unwrap();
+        assert_eq!(config.story_type.as_deref(), Some("top"));
+        assert_eq!(config.page_size, Some(20));
+        assert_eq!(config.api_url.as_deref(), Some("http://localhost:8080"));
+        let invalid = |var: &str| (var == "HN_CLI_PAGE_SIZE").then(|| "many".to_string());
+        assert!(config.apply_env(invalid).is_err());
+    }
+
+    #[test]
+    fn test_parse_theme() {
+        let theme = Theme::parse("title = \"yellow.bold\"\nurl = \"\"\n").unwrap();
+        assert_eq!(theme.title, "yellow.bold");
+        assert_eq!(theme.url, "");
+        assert_eq!(theme.author, Theme::default().author);
+        assert!(Theme::parse("unknown = \"red\"").is_err());
+        for name in BUILTIN_THEMES {
+            assert!(Theme::builtin(name).is_some());
+        }
+        assert_eq!(Theme::builtin("unknown"), None);
+
+        let monochrome = Theme::builtin("gruvbox").unwrap().monochrome();
+        assert_eq!(monochrome.title, "bold");
+        assert_eq!(monochrome.author, "");
+        assert_eq!(monochrome.muted, "");
+
+        let theme = Theme::default();
+        let style = theme.comment_author_style("pg");
+        assert!(theme.author_palette.iter().any(|color| color == style));
+        assert_eq!(theme.comment_author_style("pg"), style);
+        assert_eq!(Theme::plain().comment_author_style("pg"), "");
+    }
+
+    #[test]
+    fn test_user_flair() {
+        let flair = |karma| UserFlair {
+            karma,
+            created: 1588888888,
+        };
+        assert_eq!(flair(42).karma_bucket(), "<100");
+        assert_eq!(flair(550).karma_bucket(), "500+");
+        assert_eq!(flair(4200).karma_bucket(), "4k+");
+        assert_eq!(flair(45000).karma_bucket(), "40k+");
+        assert_eq!(flair(1).account_age(1588888888 + 90 * 86400), "2mo");
+        assert_eq!(flair(1).account_age(1588888888 + 800 * 86400), "2y");
+
+        let path = std::env::temp_dir().join(format!("hn-test-users-{}.json", std::process::id()));
+        let mut cache = FlairCache::load(&path).unwrap();
+        cache.insert("pg", flair(155000), 1000);
+        cache.save(1000).unwrap();
+        let cache = FlairCache::load(&path).unwrap();
+        assert_eq!(cache.get("pg", 2000), Some(flair(155000)));
+        assert_eq!(cache.get("pg", 1000 + 86400), None);
+        assert_eq!(cache.get("dang", 2000), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_seen_ids() {
+        let path = std::env::temp_dir().join(format!("hn-test-seen-{}.json", std::process::id()));
+        let mut seen = SeenIds::load(&path).unwrap();
+        assert_eq!(seen.mark_seen(&[3, 2, 1]), vec![3, 2, 1]);
+        assert_eq!(seen.mark_seen(&[4, 3, 2]), vec![4]);
+        seen.save().unwrap();
+
+        let mut seen = SeenIds::load(&path).unwrap();
+        assert_eq!(seen.mark_seen(&[5, 4, 1]), vec![5]);
+        assert_eq!(seen.corrupt_backup(), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_reading_progress() {
+        let path =
+            std::env::temp_dir().join(format!("hn-test-progress-{}.json", std::process::id()));
+        let mut progress = ReadingProgress::load(&path).unwrap();
+        assert_eq!(progress.last_read(1), None);
+        progress.mark_read(1, 10);
+        progress.mark_read(2, 20);
+        progress.mark_read(1, 11);
+        progress.mark_visited(1, 1588888888);
+        progress.mark_visited(3, 1588888999);
+        progress.save().unwrap();
+
+        let progress = ReadingProgress::load(&path).unwrap();
+        assert_eq!(progress.last_read(1), Some(11));
+        assert_eq!(progress.last_read(2), Some(20));
+        assert_eq!(progress.last_read(3), None);
+        assert_eq!(progress.last_visit(1), Some(1588888888));
+        assert_eq!(progress.last_visit(2), None);
+
+        std::fs::write(
+            &path,
+            r#"{"version":0,"threads":[{"story":4,"comment":40}]}"#,
+        )
+        .unwrap();
+        let progress = ReadingProgress::load(&path).unwrap();
+        assert_eq!(progress.last_read(4), Some(40));
+        assert_eq!(progress.last_visit(4), None);
+
+        let newer = r#"{"version":9,"threads":[]}"#;
+        std::fs::write(&path, newer).unwrap();
+        assert!(ReadingProgress::load(&path).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), newer);
+
+        std::fs::write(&path, "{").unwrap();
+        let progress = ReadingProgress::load(&path).unwrap();
+        let backup = progress.corrupt_backup().unwrap().to_path_buf();
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "{");
+        assert_eq!(progress.last_read(4), None);
+        std::fs::remove_file(&backup).unwrap();
+    }
+
+    #[test]
+    fn test_activity_log() {
+        let path =
+            std::env::temp_dir().join(format!("hn-test-activity-{}.json", std::process::id()));
+        let mut log = ActivityLog::load(&path).unwrap();
+        log.record(ActivityEvent {
+            timestamp: 1588888888,
+            message: "new on top: Story".to_string(),
+            item: Some(42),
+        });
+        log.save().unwrap();
+
+        let mut log = ActivityLog::load(&path).unwrap();
+        assert_eq!(log.events().len(), 1);
+        assert!(log.events()[0]
+            .to_string()
+            .ends_with(" new on top: Story (hn 42)"));
+        log.clear();
+        log.save().unwrap();
+        assert!(ActivityLog::load(&path).unwrap().events().is_empty());
+
+        // a newer release's log is refused rather than saved over
+        let newer = r#"{"version":9,"events":[]}"#;
+        std::fs::write(&path, newer).unwrap();
+        assert!(ActivityLog::load(&path).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), newer);
+
+        std::fs::write(&path, r#"{"events":"#).unwrap();
+        let log = ActivityLog::load(&path).unwrap();
+        let backup = log.corrupt_backup().unwrap().to_path_buf();
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), r#"{"events":"#);
+        assert!(log.events().is_empty());
+        assert!(!path.exists());
+        std::fs::remove_file(&backup).unwrap();
+    }
+
+    #[test]
+    fn test_bookmarks() {
+        let path =
+            std::env::temp_dir().join(format!("hn-test-bookmarks-{}.json", std::process::id()));
+        let bookmark = |item, snippet: &str| Bookmark {
+            item,
+            story: 1,
+            title: "Story".to_string(),
+            author: "me".to_string(),
+            snippet: snippet.to_string(),
+            url: format!("https://news.ycombinator.com/item?id={}", item),
+            saved_at: 1588888888,
+        };
+        let mut bookmarks = Bookmarks::load(&path).unwrap();
+        bookmarks.add(bookmark(2, "First"));
+        bookmarks.add(bookmark(3, "Second"));
+        bookmarks.add(bookmark(2, "Edited"));
+        bookmarks.save().unwrap();
+
+        let mut bookmarks = Bookmarks::load(&path).unwrap();
+        let snippets: Vec<_> = bookmarks
+            .list()
+            .iter()
+            .map(|b| b.snippet.as_str())
+            .collect();
+        assert_eq!(snippets, vec!["Second", "Edited"]);
+        assert_eq!(
+            bookmarks.list()[0].to_string(),
+            "me: Second\n  in Story -> https://news.ycombinator.com/item?id=3"
+        );
+        assert!(bookmarks.remove(3));
+        assert!(!bookmarks.remove(3));
+        std::fs::write(&path, "not json").unwrap();
+        let bookmarks = Bookmarks::load(&path).unwrap();
+        let backup = bookmarks.corrupt_backup().unwrap().to_path_buf();
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "not json");
+        assert!(bookmarks.list().is_empty());
+        std::fs::remove_file(&backup).unwrap();
+    }
+
+    #[test]
+    fn test_seen_ids_migration() {
+        let path = std::env::temp_dir().join(format!("hn-test-legacy-{}.json", std::process::id()));
+        std::fs::write(&path, "[1,2]").unwrap();
+        let mut seen = SeenIds::load(&path).unwrap();
+        assert_eq!(seen.corrupt_backup(), None);
+        assert_eq!(seen.mark_seen(&[2, 3]), vec![3]);
+
+        std::fs::write(&path, r#"{"version":99,"ids":[1,2]}"#).unwrap();
+        assert!(SeenIds::load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_seen_ids_corrupted() {
+        let path =
+            std::env::temp_dir().join(format!("hn-test-corrupt-{}.json", std::process::id()));
+        std::fs::write(&path, "[1, 2,").unwrap();
+
+        let mut seen = SeenIds::load(&path).unwrap();
+        let backup = seen.corrupt_backup().unwrap().to_path_buf();
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "[1, 2,");
+        assert!(!path.exists());
+        assert_eq!(seen.mark_seen(&[2, 3]), vec![2, 3]);
+        seen.save().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            r#"{"version":1,"ids":[2,3]}"#
+        );
+
+        std::fs::write(&path, "{").unwrap();
+        let seen = SeenIds::load(&path).unwrap();
+        let second_backup = seen.corrupt_backup().unwrap().to_path_buf();
+        assert_ne!(second_backup, backup);
+        assert_eq!(std::fs::read_to_string(&second_backup).unwrap(), "{");
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "[1, 2,");
+
+        std::fs::remove_file(&backup).unwrap();
+        std::fs::remove_file(&second_backup).unwrap();
+    }
+
+    #[test]
+    fn test_rate_limit_wait_time() {
+        use crate::rate_limit::wait_time;
+        use std::collections::VecDeque;
+        use std::time::{Duration, Instant};
+
+        let now = Instant::now() + Duration::from_secs(120);
+        let mut sent = VecDeque::from([
+            now - Duration::from_secs(70),
+            now - Duration::from_secs(50),
+            now - Duration::from_secs(10),
+        ]);
+        assert_eq!(wait_time(&mut sent, now, 3), None);
+        assert_eq!(sent.len(), 2);
+        sent.push_back(now);
+        assert_eq!(wait_time(&mut sent, now, 3), Some(Duration::from_secs(10)));
+    }
+
     #[test]
     fn test_unix_epoch_to_datetime() {
         let dt = chrono::DateTime::from_timestamp(1588888888, 0).unwrap();
@@ -133,18 +1251,42 @@ mod tests {
         assert_eq!(time_ago(now - 3600), "1 hours ago");
         assert_eq!(time_ago(now - 86400), "1 days ago");
         assert_eq!(time_ago(now - 604800), "1 weeks ago");
+        assert_eq!(time_ago(now + 60), "0 seconds ago");
+    }
+
+    #[test]
+    fn test_age_bucket() {
+        let now = now();
+        assert_eq!(age_bucket(now), "less than an hour ago");
+        assert_eq!(age_bucket(now - 3599), "less than an hour ago");
+        assert_eq!(age_bucket(now + 60), "less than an hour ago");
+        assert_eq!(age_bucket(now - 7200), "2 hours ago");
+        assert_eq!(age_bucket(now - 3 * 86400), "3 days ago");
+    }
+
+    #[test]
+    fn test_page_ids() {
+        let ids: Vec<i64> = (1..=25).collect();
+        assert_eq!(page_ids(&ids, 1, 10), &ids[..10]);
+        assert_eq!(page_ids(&ids, 3, 10), &ids[20..]);
+        assert!(page_ids(&ids, 4, 10).is_empty());
     }
 
     #[test]
     fn test_display() {
         let item = HNCLIItem {
+            id: 1,
             title: "Rust is awesome".to_string(),
             url: "https://rust-lang.org".to_string(),
             author: "me".to_string(),
             time: "2020-05-07 22:01:28".to_string(),
             time_ago: "0 seconds ago".to_string(),
-            score: 9,
+            timestamp: 1588888888,
+            score: Some(9),
             comments: Some(1),
+            is_job: false,
+            is_launch: false,
+            poll_options: Vec::new(),
         };
         assert_eq!(
             item.to_string(),
@@ -152,6 +1294,689 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_display_job() {
+        let item = HNCLIItem {
+            id: 1,
+            title: "Rust Inc. is hiring".to_string(),
+            url: "https://news.ycombinator.com/item?id=1".to_string(),
+            author: "me".to_string(),
+            time: "2020-05-07 22:01:28".to_string(),
+            time_ago: "0 seconds ago".to_string(),
+            timestamp: 1588888888,
+            score: None,
+            comments: None,
+            is_job: true,
+            is_launch: false,
+            poll_options: Vec::new(),
+        };
+        assert_eq!(
+            item.to_string(),
+            "Rust Inc. is hiring by me\n[job] - 0 seconds ago\n-> https://news.ycombinator.com/item?id=1"
+        );
+    }
+
+    #[test]
+    fn test_display_launch() {
+        let item = HNCLIItem {
+            id: 1,
+            title: "Launch HN: Rust Inc. (YC W24) - Memory safety for everyone".to_string(),
+            url: "https://rust.inc".to_string(),
+            author: "me".to_string(),
+            time: "2020-05-07 22:01:28".to_string(),
+            time_ago: "0 seconds ago".to_string(),
+            timestamp: 1588888888,
+            score: Some(9),
+            comments: Some(1),
+            is_job: false,
+            is_launch: is_launch_title(
+                "Launch HN: Rust Inc. (YC W24) - Memory safety for everyone",
+            ),
+            poll_options: Vec::new(),
+        };
+        assert!(item.is_launch);
+        assert_eq!(
+            item.to_string(),
+            "Launch HN: Rust Inc. (YC W24) - Memory safety for everyone by me\n[launch] - [9 points] - 1 comments - 0 seconds ago\n-> https://rust.inc"
+        );
+        assert!(!is_launch_title("Ask HN: Launch HN etiquette?"));
+    }
+
+    #[test]
+    fn test_display_poll() {
+        let item = HNCLIItem {
+            id: 1,
+            title: "Poll: Favorite language?".to_string(),
+            url: "https://news.ycombinator.com/item?id=1".to_string(),
+            author: "me".to_string(),
+            time: "2020-05-07 22:01:28".to_string(),
+            time_ago: "0 seconds ago".to_string(),
+            timestamp: 1588888888,
+            score: Some(9),
+            comments: Some(1),
+            is_job: false,
+            is_launch: false,
+            poll_options: vec![
+                PollOption {
+                    text: "Rust".to_string(),
+                    score: 42,
+                },
+                PollOption {
+                    text: "Go".to_string(),
+                    score: 7,
+                },
+            ],
+        };
+        assert_eq!(
+            item.to_string(),
+            "Poll: Favorite language? by me\n[9 points] - 1 comments - 0 seconds ago\n  o Rust (42 points)\n  o Go (7 points)\n-> https://news.ycombinator.com/item?id=1"
+        );
+    }
+
+    #[test]
+    fn test_display_comment_tree() {
+        let mut comment = HNCLIComment {
+            id: 1,
+            author: "me".to_string(),
+            body: CommentBody::parse("Rust is awesome<p>Indeed"),
+            time_ago: "1 hours ago".to_string(),
+            timestamp: 1588888888,
+            deleted: false,
+            dead: false,
+            collapsed: false,
+            reply_count: 2,
+            replies: vec![
+                HNCLIComment {
+                    id: 2,
+                    author: "you".to_string(),
+                    body: CommentBody::parse(
+                        "Agreed, see <a href=\"https://rust-lang.org\">this</a>",
+                    ),
+                    time_ago: "0 seconds ago".to_string(),
+                    timestamp: 1588888888,
+                    deleted: false,
+                    dead: false,
+                    collapsed: false,
+                    reply_count: 3,
+                    replies: Vec::new(),
+                },
+                HNCLIComment {
+                    id: 3,
+                    author: "".to_string(),
+                    body: CommentBody::default(),
+                    time_ago: "0 seconds ago".to_string(),
+                    timestamp: 1588888888,
+                    deleted: true,
+                    dead: false,
+                    collapsed: false,
+                    reply_count: 0,
+                    replies: Vec::new(),
+                },
+                HNCLIComment {
+                    id: 4,
+                    author: "spammer".to_string(),
+                    body: CommentBody::parse("Buy now"),
+                    time_ago: "0 seconds ago".to_string(),
+                    timestamp: 1588888888,
+                    deleted: false,
+                    dead: true,
+                    collapsed: false,
+                    reply_count: 0,
+                    replies: Vec::new(),
+                },
+            ],
+        };
+        assert_eq!(
+            comment.to_string(),
+            "me - 1 hours ago\nRust is awesome\n\nIndeed\n\n  you - 0 seconds ago\n  Agreed, see this[1]\n\n  [1] https://rust-lang.org\n    [+3 replies]\n\n  [deleted]\n\n  spammer [dead] - 0 seconds ago\n  Buy now\n"
+        );
+        comment.hide_dead();
+        assert!(comment
+            .to_string()
+            .ends_with("\n\n  [deleted]\n\n  [deleted]\n"));
+    }
+
+    #[test]
+    fn test_comment_record_hides_dead() {
+        let comment = || HNCLIComment {
+            id: 4,
+            author: "spammer".to_string(),
+            body: CommentBody::parse("Buy now"),
+            time_ago: "0 seconds ago".to_string(),
+            timestamp: 1588888888,
+            deleted: false,
+            dead: true,
+            collapsed: false,
+            reply_count: 0,
+            replies: Vec::new(),
+        };
+        let record = comment_record(1, 2, comment(), false);
+        assert!(record.comment.deleted);
+        assert_eq!(record.comment.body, CommentBody::default());
+        let json = serde_json::to_string(&ThreadEntry::Comment(record)).unwrap();
+        assert!(!json.contains("Buy now"));
+
+        let record = comment_record(1, 2, comment(), true);
+        assert!(!record.comment.deleted);
+        assert_eq!(record.comment.body.to_string(), "Buy now");
+    }
+
+    #[test]
+    fn test_display_flat_thread() {
+        let comment = |id, author: &str, timestamp, replies| HNCLIComment {
+            id,
+            author: author.to_string(),
+            body: CommentBody::parse(&format!("Comment {}", id)),
+            time_ago: "1 hours ago".to_string(),
+            timestamp,
+            deleted: false,
+            dead: false,
+            collapsed: false,
+            reply_count: 1,
+            replies,
+        };
+        let mut thread = HNCLIThread {
+            story: HNCLIItem {
+                id: 1,
+                title: "Story".to_string(),
+                url: "https://example.com".to_string(),
+                author: "op".to_string(),
+                time: "2020-05-07 22:01:28".to_string(),
+                time_ago: "1 hours ago".to_string(),
+                timestamp: 1588888888,
+                score: None,
+                comments: None,
+                is_job: false,
+                is_launch: false,
+                poll_options: Vec::new(),
+            },
+            breadcrumb: vec![Crumb {
+                id: 7,
+                label: "Parent story".to_string(),
+            }],
+            text: Some(CommentBody::parse(
+                "See <a href=\"https://a.com\">a</a> and <a href=\"https://a.com\">a</a>",
+            )),
+            previous: Vec::new(),
+            flairs: HashMap::new(),
+            window: CommentWindow::default(),
+            comment_count: 2,
+            flat: true,
+            new_since: Some(15),
+            resumed: false,
+            comments: vec![
+                comment(2, "me", 10, vec![comment(4, "you", 30, Vec::new())]),
+                comment(3, "op", 20, Vec::new()),
+            ],
+        };
+        assert!(thread
+            .to_string()
+            .contains("-> https://example.com\nin Parent story\n[up: hn 7]\n"));
+        assert!(thread.to_string().ends_with(
+            "\n\nme - 1 hours ago\nComment 2\n\nop [OP] [new] - 1 hours ago\nComment 3\n\nyou [new] - 1 hours ago - in reply to me\n^ Comment 2\nComment 4"
+        ));
+        assert_eq!(
+            thread.spoken_text(),
+            "Story, by op.\n\nSee a and a\n\nme says: Comment 2\n\nop says: Comment 3"
+        );
+        assert_eq!(thread.new_count(), 2);
+        assert_eq!(
+            thread.links(),
+            vec![ThreadLink {
+                url: "https://a.com".to_string(),
+                author: "op".to_string(),
+                item: 1,
+            }]
+        );
+
+        thread.window.limit = Some(2);
+        thread.comment_count = 5;
+        assert!(thread
+            .to_string()
+            .ends_with("\n\n[+3 more comments: hn 1 --skip 2 --per-level 2]"));
+        thread.resumed = true;
+        assert!(thread
+            .to_string()
+            .ends_with("\n\n[+3 more comments: hn 1 --resume --per-level 2]"));
+    }
+
+    #[test]
+    fn test_collapse_comments() {
+        let comment = |id, author: &str, text: &str, replies| HNCLIComment {
+            id,
+            author: author.to_string(),
+            body: CommentBody::parse(text),
+            time_ago: "1 hours ago".to_string(),
+            timestamp: 1588888888,
+            deleted: text.is_empty(),
+            dead: false,
+            collapsed: false,
+            reply_count: 1,
+            replies,
+        };
+        let mut thread = comment(
+            1,
+            "me",
+            "A long enough comment",
+            vec![
+                comment(2, "you", "This!", Vec::new()),
+                comment(3, "troll", "Bait", Vec::new()),
+                comment(4, "", "", vec![comment(5, "", "", Vec::new())]),
+            ],
+        );
+        let rules = Config::parse(
+            "[collapse]\nmin_length = 3\nmuted_users = [\"troll\"]\ndeleted = true\nlow_effort = true\n",
+        )
+        .unwrap()
+        .collapse;
+        thread.collapse(&rules);
+        assert!(!thread.collapsed);
+        let collapsed: Vec<_> = thread.replies.iter().map(|reply| reply.collapsed).collect();
+        assert_eq!(collapsed, vec![true, true, true]);
+        assert!(thread.to_string().ends_with(
+            "\n\n  troll [collapsed, 1 replies]\n\n  [deleted] [collapsed, 1 replies, 2+ in all]\n"
+        ));
+        assert_eq!(thread.descendant_count(), 7);
+    }
+
+    #[test]
+    fn test_grep_comments() {
+        let comment = |id, author: &str, text: &str, replies| HNCLIComment {
+            id,
+            author: author.to_string(),
+            body: CommentBody::parse(text),
+            time_ago: "1 hours ago".to_string(),
+            timestamp: 1588888888,
+            deleted: false,
+            dead: false,
+            collapsed: true,
+            reply_count: 1,
+            replies,
+        };
+        let mut comments = vec![
+            comment(
+                1,
+                "me",
+                "Nothing here",
+                vec![
+                    comment(2, "you", "Try <i>Rust</i>, rust is great", Vec::new()),
+                    comment(3, "them", "Go", Vec::new()),
+                ],
+            ),
+            comment(4, "rustacean", "Hello", Vec::new()),
+            comment(5, "other", "Unrelated", Vec::new()),
+        ];
+        assert_eq!(grep_comments(&mut comments, "RUST"), 2);
+        let ids: Vec<_> = comments.iter().map(|comment| comment.id).collect();
+        assert_eq!(ids, vec![1, 4]);
+        assert!(!comments[0].collapsed);
+        assert_eq!(comments[0].replies.len(), 1);
+        assert_eq!(
+            comments[0].replies[0].body.blocks,
+            vec![Block::Paragraph(vec![
+                Span::plain("Try "),
+                Span {
+                    text: "Rust".to_string(),
+                    style: SpanStyle::Match
+                },
+                Span::plain(", "),
+                Span {
+                    text: "rust".to_string(),
+                    style: SpanStyle::Match
+                },
+                Span::plain(" is great"),
+            ])]
+        );
+        assert_eq!(comments[1].body.to_string(), "Hello");
+
+        // a match across styles is highlighted as a whole, one in a code block is not searched
+        let mut comments = vec![
+            comment(1, "me", "Use <i>cargo</i> build", Vec::new()),
+            comment(2, "you", "Run <code>cargo build</code>", Vec::new()),
+            comment(3, "them", "<pre><code>cargo build</code></pre>", Vec::new()),
+        ];
+        assert_eq!(grep_comments(&mut comments, "cargo build"), 2);
+        let ids: Vec<_> = comments.iter().map(|comment| comment.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+        let matched = |spans: &[Span]| -> Vec<String> {
+            (spans.iter())
+                .filter(|span| span.style == SpanStyle::Match)
+                .map(|span| span.text.clone())
+                .collect()
+        };
+        for comment in &comments {
+            let Block::Paragraph(spans) = &comment.body.blocks[0] else {
+                panic!("not a paragraph");
+            };
+            assert_eq!(matched(spans), vec!["cargo build"]);
+        }
+        assert_eq!(comments[0].body.blocks[0].text(), "Use cargo build");
+        let body = CommentBody::parse("See <a href=\"https://a.com\">the site</a>");
+        assert!(body.contains("THE SITE"));
+        assert!(!body.contains("site[1]"));
+
+        let mut comments = vec![
+            comment(
+                1,
+                "me",
+                "A",
+                vec![comment(
+                    2,
+                    "you",
+                    "B",
+                    vec![comment(3, "me", "C", Vec::new())],
+                )],
+            ),
+            comment(4, "you", "D", Vec::new()),
+            comment(5, "meme", "E", Vec::new()),
+        ];
+        assert_eq!(filter_by_author(&mut comments, "me"), 2);
+        assert_eq!(comments.len(), 1);
+        assert!(!comments[0].replies[0].collapsed);
+        assert_eq!(comments[0].replies[0].replies[0].id, 3);
+    }
+
+    #[test]
+    fn test_comment_window() {
+        let ids = [1, 2, 3, 4, 5];
+        assert_eq!(CommentWindow::default().apply(&ids, true), &ids);
+        let window = CommentWindow {
+            skip: 2,
+            limit: Some(2),
+            after: None,
+        };
+        assert_eq!(window.apply(&ids, true), &[3, 4]);
+        assert_eq!(window.apply(&ids, false), &[1, 2]);
+        assert!(window.apply(&ids[..1], true).is_empty());
+        let window = CommentWindow {
+            after: Some(4),
+            ..window
+        };
+        assert_eq!(window.resolve(&ids).apply(&ids, true), &[5]);
+        assert_eq!(
+            window.resolve(&ids[..3]),
+            CommentWindow {
+                after: None,
+                ..window
+            }
+        );
+    }
+
+    #[test]
+    fn test_truncate_comment_lines() {
+        let mut comment = HNCLIComment {
+            id: 1,
+            author: "me".to_string(),
+            body: CommentBody {
+                blocks: vec![Block::Paragraph(vec![Span::plain("one\ntwo\nthree\nfour")])],
+                links: Vec::new(),
+            },
+            time_ago: "1 hours ago".to_string(),
+            timestamp: 1588888888,
+            deleted: false,
+            dead: false,
+            collapsed: false,
+            reply_count: 0,
+            replies: Vec::new(),
+        };
+        comment.truncate_lines(4);
+        assert_eq!(comment.body.to_string(), "one\ntwo\nthree\nfour");
+        comment.truncate_lines(2);
+        assert_eq!(comment.body.to_string(), "one\ntwo\n\n[... 2 more lines]");
+    }
+
+    #[test]
+    fn test_raw_parent_id() {
+        assert_eq!(
+            raw_parent_id(&serde_json::json!({ "id": 2, "parent": 1 })),
+            Some(1)
+        );
+        assert_eq!(raw_parent_id(&serde_json::json!({ "id": 1 })), None);
+    }
+
+    #[test]
+    fn test_raw_crumb() {
+        let story = serde_json::json!({ "id": 1, "by": "op", "title": "Story", "type": "story" });
+        let comment = serde_json::json!({ "id": 2, "by": "me", "parent": 1, "type": "comment" });
+        let deleted = serde_json::json!({ "id": 3, "deleted": true, "parent": 2 });
+        let labels: Vec<_> = [story, comment, deleted]
+            .iter()
+            .map(|item| raw_crumb(item).label)
+            .collect();
+        assert_eq!(labels, vec!["Story", "me", "[deleted]"]);
+    }
+
+    #[test]
+    fn test_item_inspection_display() {
+        let inspection = ItemInspection {
+            item: serde_json::json!({ "id": 3, "type": "comment", "by": "you", "parent": 2 }),
+            parents: vec![
+                serde_json::json!({ "id": 2, "type": "comment", "by": "me", "parent": 1 }),
+                serde_json::json!({ "id": 1, "type": "story", "by": "pg", "title": "Hi" }),
+            ],
+            depth: 2,
+            descendants: 0,
+        };
+        let text = inspection.to_string();
+        assert!(text.starts_with(
+            "3 comment by you\ndepth: 2 - descendants: 0\n^ 2 comment by me\n  ^ 1 story by pg: Hi\n\n{"
+        ));
+    }
+
+    #[test]
+    fn test_wrap_comment_body() {
+        let mut body = CommentBody::parse(
+            "one two three four<p>&gt; five six seven<p><pre><code>  let long_line = 1;</code></pre>",
+        );
+        body.wrap(10);
+        assert_eq!(
+            body.to_string(),
+            "one two\nthree four\n\n> five six\n> seven\n\n  let long_line = 1;"
+        );
+    }
+
+    #[test]
+    fn test_parse_comment_body() {
+        let body = CommentBody::parse(
+            "Hello &amp; <i>bye</i><p>&gt; quoted<p>See <a href=\"https:&#x2F;&#x2F;x.com\">x.com</a> or <code>ls</code><p><pre><code>  fn main() {}\n</code></pre>After",
+        );
+        let link = SpanStyle::Link {
+            href: "https://x.com".to_string(),
+        };
+        assert_eq!(
+            body.blocks,
+            vec![
+                Block::Paragraph(vec![
+                    Span::plain("Hello & "),
+                    Span {
+                        text: "bye".to_string(),
+                        style: SpanStyle::Italic,
+                    },
+                ]),
+                Block::Quote(vec![Span::plain("quoted")]),
+                Block::Paragraph(vec![
+                    Span::plain("See "),
+                    Span {
+                        text: "x.com".to_string(),
+                        style: link,
+                    },
+                    Span {
+                        text: "[1]".to_string(),
+                        style: SpanStyle::Marker,
+                    },
+                    Span::plain(" or "),
+                    Span {
+                        text: "ls".to_string(),
+                        style: SpanStyle::Code,
+                    },
+                ]),
+                Block::Code("  fn main() {}".to_string()),
+                Block::Paragraph(vec![Span::plain("After")]),
+            ]
+        );
+        assert_eq!(body.links, vec!["https://x.com"]);
+        assert_eq!(
+            body.to_string(),
+            "Hello & bye\n\n> quoted\n\nSee x.com[1] or ls\n\n  fn main() {}\n\nAfter"
+        );
+    }
+
+    #[test]
+    fn test_items_to_csv() {
+        let item = HNCLIItem {
+            id: 1,
+            title: "Rust, \"the\" language".to_string(),
+            url: "https://rust-lang.org".to_string(),
+            author: "me".to_string(),
+            time: "2020-05-07 22:01:28".to_string(),
+            time_ago: "0 seconds ago".to_string(),
+            timestamp: 1588888888,
+            score: Some(9),
+            comments: None,
+            is_job: false,
+            is_launch: false,
+            poll_options: Vec::new(),
+        };
+        assert_eq!(
+            items_to_csv(&[item]),
+            "id,title,url,author,score,comments,time\n1,\"Rust, \"\"the\"\" language\",https://rust-lang.org,me,9,,2020-05-07 22:01:28\n"
+        );
+    }
+
+    #[test]
+    fn test_velocity() {
+        let item = |score, age: u64| HNCLIItem {
+            id: 1,
+            title: "Story".to_string(),
+            url: "https://example.com".to_string(),
+            author: "me".to_string(),
+            time: "2020-05-07 22:01:28".to_string(),
+            time_ago: "0 seconds ago".to_string(),
+            timestamp: 1588888888 - age,
+            score: Some(score),
+            comments: None,
+            is_job: false,
+            is_launch: false,
+            poll_options: Vec::new(),
+        };
+        assert_eq!(item(30, 3 * 3600).velocity(1588888888), 10.0);
+        assert_eq!(item(5, 60).velocity(1588888888), 5.0);
+        assert_eq!(item(5, 0).velocity(1588888888 - 60), 5.0);
+    }
+
+    #[test]
+    fn test_group_by_domain() {
+        let items: Vec<_> = [
+            "https://arxiv.org/abs/1",
+            "https://www.rust-lang.org",
+            "http://arxiv.org/abs/2?v=1",
+        ]
+        .iter()
+        .map(|url| HNCLIItem {
+            id: 1,
+            title: "Paper".to_string(),
+            url: url.to_string(),
+            author: "me".to_string(),
+            time: "2020-05-07 22:01:28".to_string(),
+            time_ago: "0 seconds ago".to_string(),
+            timestamp: 1588888888,
+            score: None,
+            comments: None,
+            is_job: false,
+            is_launch: false,
+            poll_options: Vec::new(),
+        })
+        .collect();
+        assert_eq!(items[1].domain(), "rust-lang.org");
+        let items: Vec<_> = items.iter().collect();
+        assert_eq!(
+            group_by_domain(&items),
+            vec![("arxiv.org", vec![0, 2]), ("rust-lang.org", vec![1])]
+        );
+    }
+
+    #[test]
+    fn test_extract_topics() {
+        assert_eq!(
+            topic_terms("Show HN: The Rust and C++ book, 2024 edition (rust)"),
+            vec!["rust", "c++", "book", "edition"]
+        );
+        let titles = [
+            "Rust in the kernel",
+            "Why Rust?",
+            "A kernel written in Zig",
+            "Zig 0.12",
+            "Rust 2024",
+        ];
+        assert_eq!(
+            extract_topics(titles, 2),
+            vec![
+                Topic {
+                    term: "rust".to_string(),
+                    count: 3
+                },
+                Topic {
+                    term: "kernel".to_string(),
+                    count: 2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_item_row_ids() {
+        let html = "<table><tr class='athing submission' id='42'><td>Rust</td></tr>\
+            <tr class=\"spacer\"></tr><tr class=\"athing comtr\" id=\"43\"></tr></table>";
+        assert_eq!(crate::hn_client::parse_item_row_ids(html), vec![42, 43]);
+    }
+
+    #[test]
+    fn test_check_username() {
+        use crate::hn_client::check_username;
+        assert!(check_username("pg").is_ok());
+        assert!(check_username("some_user-1").is_ok());
+        assert!(check_username("").is_err());
+        assert!(check_username("../item/1").is_err());
+        assert!(check_username("pg&p=2").is_err());
+        assert!(check_username("pg?").is_err());
+    }
+
+    #[test]
+    fn test_deserialize_item_relations() {
+        let option: HackerNewsItem = serde_json::from_str(
+            r#"{"id": 3, "by": "me", "time": 1588888888, "type": "pollopt", "poll": 1, "score": 5, "text": "Rust"}"#,
+        )
+        .unwrap();
+        assert_eq!(option.poll, Some(1));
+        assert_eq!(option.parent, None);
+
+        let comment: HackerNewsItem = serde_json::from_str(
+            r#"{"id": 4, "by": "you", "time": 1588888888, "type": "comment", "parent": 2}"#,
+        )
+        .unwrap();
+        assert_eq!(comment.parent, Some(2));
+    }
+
+    #[test]
+    fn test_recover_item() {
+        let item = HackerNewsItem::recover(
+            5,
+            serde_json::json!({ "by": "me", "time": "yesterday", "score": null, "kids": [6, "7"] }),
+        );
+        assert_eq!(item.id, 5);
+        assert_eq!(item.by, "me");
+        assert_eq!(item.time, 0);
+        assert_eq!(item.score, None);
+        assert_eq!(item.kids, None);
+
+        let missing = HackerNewsItem::recover(6, serde_json::Value::Null);
+        assert_eq!(missing.id, 6);
+        assert!(missing.deleted);
+        let odd = HackerNewsItem::recover(7, serde_json::json!([1, 2]));
+        assert_eq!(odd.id, 7);
+        assert!(odd.deleted);
+    }
+
     #[test]
     fn test_get_item_url() {
         let item = HackerNewsItem {
@@ -160,8 +1985,14 @@ mod tests {
             time: 1588888888,
             kids: None,
             url: Some("https://rust-lang.org".to_string()),
-            score: 9,
+            score: Some(9),
             title: "Rust is awesome".to_string(),
+            text: None,
+            parts: None,
+            parent: None,
+            poll: None,
+            deleted: false,
+            dead: false,
             descendants: Some(1),
             r#type: "story".to_string(),
         };
@@ -177,6 +2008,122 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_plain_text() {
+        let item = HackerNewsItem {
+            id: 3,
+            by: "me".to_string(),
+            time: 1588888888,
+            kids: None,
+            url: None,
+            score: None,
+            title: "".to_string(),
+            text: Some(
+                "See <a href=\"https://rust-lang.org\">the site</a><p>&gt; quoted".to_string(),
+            ),
+            parts: None,
+            parent: Some(1),
+            poll: None,
+            deleted: false,
+            dead: false,
+            descendants: None,
+            r#type: "comment".to_string(),
+        };
+        assert_eq!(
+            plain_text(&item),
+            "See the site[1]\n\n> quoted\n\n[1] https://rust-lang.org"
+        );
+
+        let item = HackerNewsItem {
+            title: "Ask HN: Rust?".to_string(),
+            text: None,
+            ..item
+        };
+        assert_eq!(plain_text(&item), "Ask HN: Rust?");
+        let service = HackerNewsCliServiceImpl::new(None);
+        assert_eq!(
+            service.permalink(3),
+            "https://news.ycombinator.com/item?id=3"
+        );
+    }
+
+    #[test]
+    fn test_get_item_title() {
+        let item = HackerNewsItem {
+            id: 2,
+            by: "me".to_string(),
+            time: 1588888888,
+            kids: None,
+            url: None,
+            score: None,
+            title: "".to_string(),
+            text: Some("Rust is <i>awesome</i><p>Second paragraph".to_string()),
+            parts: None,
+            parent: None,
+            poll: None,
+            deleted: false,
+            dead: false,
+            descendants: None,
+            r#type: "comment".to_string(),
+        };
+
+        let service = HackerNewsCliServiceImpl::new(None);
+        assert_eq!(service.get_item_title(&item), "[comment] Rust is awesome");
+
+        let item = HackerNewsItem {
+            title: "Rust is awesome".to_string(),
+            ..item
+        };
+        assert_eq!(service.get_item_title(&item), "Rust is awesome");
+    }
+
+    #[test]
+    fn test_decode_html() {
+        assert_eq!(
+            decode_html("It&#x27;s <a href=\"https:&#x2F;&#x2F;rust-lang.org\">here</a><p>&quot;a &lt; b&quot;"),
+            "It's here\n\n\"a < b\""
+        );
+        assert_eq!(snippet("\n\nfirst line\nsecond", 5), "first...");
+    }
+
+    #[test]
+    fn test_parse_listing() {
+        let html = "Rust Inc. | Backend Engineer | Paris, France | REMOTE (EU) | $120k<p>We build things. Apply at <a href=\"https:&#x2F;&#x2F;rust.inc&#x2F;jobs\" rel=\"nofollow\">https:&#x2F;&#x2F;rust.inc&#x2F;jobs</a>";
+        let listing = parse_listing(1, "me", html).unwrap();
+
+        assert_eq!(listing.company, "Rust Inc.");
+        assert_eq!(listing.location.as_deref(), Some("Paris, France"));
+        assert!(listing.remote);
+        assert!(!listing.onsite);
+        assert!(!listing.visa);
+        assert_eq!(listing.link.as_deref(), Some("https://rust.inc/jobs"));
+        assert_eq!(
+            listing.to_string(),
+            "Rust Inc. | Paris, France | REMOTE\n-> https://rust.inc/jobs"
+        );
+
+        assert!(parse_listing(2, "me", "Is anyone hiring juniors this month?").is_none());
+    }
+
+    #[test]
+    fn test_listings_to_csv() {
+        let listing = JobListing {
+            id: 1,
+            author: "me".to_string(),
+            company: "Rust, \"Inc.\"".to_string(),
+            location: None,
+            remote: true,
+            onsite: false,
+            visa: true,
+            link: Some("https://rust.inc".to_string()),
+            header: "Rust, \"Inc.\" | REMOTE".to_string(),
+        };
+        assert_eq!(
+            listings_to_csv(&[listing]),
+            "id,author,company,location,remote,onsite,visa,link\n1,me,\"Rust, \"\"Inc.\"\"\",,true,false,true,https://rust.inc\n"
+        );
+    }
+
     #[test]
     fn test_to_hn_cli_item() {
         let now = now();
@@ -187,8 +2134,14 @@ mod tests {
             time: now,
             kids: None,
             url: Some("https://rust-lang.org".to_string()),
-            score: 9,
+            score: Some(9),
             title: "Rust is awesome".to_string(),
+            text: None,
+            parts: None,
+            parent: None,
+            poll: None,
+            deleted: false,
+            dead: false,
             descendants: Some(1),
             r#type: "story".to_string(),
         };
@@ -201,10 +2154,37 @@ mod tests {
         assert_eq!(item.author, "me");
         assert_eq!(item.time, unix_epoch_to_datetime(now));
         assert_eq!(item.time_ago, "0 seconds ago");
-        assert_eq!(item.score, 9);
+        assert_eq!(item.timestamp, now);
+        assert_eq!(item.score, Some(9));
+        assert!(!item.is_job);
         assert_eq!(item.comments, Some(1));
     }
 
+    #[test]
+    fn test_search_hit_to_hn_cli_item() {
+        let service = HackerNewsCliServiceImpl::new(None);
+        let now = now();
+
+        let hit = SearchHit {
+            object_id: "42".to_string(),
+            title: Some("Rust is awesome".to_string()),
+            url: Some("".to_string()),
+            author: "me".to_string(),
+            points: Some(9),
+            num_comments: None,
+            created_at_i: now,
+        };
+
+        let item = service.search_hit_to_hn_cli_item(hit);
+
+        assert_eq!(item.title, "Rust is awesome");
+        assert_eq!(item.url, "https://news.ycombinator.com/item?id=42");
+        assert_eq!(item.author, "me");
+        assert_eq!(item.time_ago, "0 seconds ago");
+        assert_eq!(item.score, Some(9));
+        assert_eq!(item.comments, None);
+    }
+
     #[tokio::test]
     #[ignore]
     // broken for now as we can't use dynamic dispatch with async traits
@@ -219,10 +2199,16 @@ mod tests {
         hn_client.expect_get_items().times(1).returning(|_| {
             vec![Ok(HackerNewsItem {
                 by: "".to_string(),
-                score: 0,
+                score: None,
                 time: 0,
                 title: "".to_string(),
                 url: None,
+                text: None,
+                parts: None,
+                parent: None,
+                poll: None,
+                deleted: false,
+                dead: false,
                 descendants: None,
                 id: 0,
                 kids: None,
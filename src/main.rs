@@ -1,60 +1,1487 @@
 extern crate hn_lib;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, BufReader, Lines, Stdin};
 
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{bail, Context, Result};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use console::Term;
 
-use hn_lib::{HackerNewsCliService, HackerNewsCliServiceImpl};
+use serde::Serialize;
+
+use hn_lib::{
+    extract_topics, filter_by_author, grep_comments, group_by_domain, items_to_csv,
+    listings_to_csv, prune_deleted, topic_terms, ActivityEvent, ActivityLog, Bookmarks,
+    CollapseRules, CommentWindow, Config, FlairCache, HNCLIItem, HNCLIThread, HackerNewsCliService,
+    HackerNewsCliServiceImpl, HackerNewsClientImpl, Paths, ReadingProgress, SeenIds, Theme, Themed,
+    ThreadEntry, UserFlair, BUILTIN_THEMES,
+};
+
+/// The size of the pages the topics are extracted from
+const TOPICS_PAGE_SIZE: u8 = 30;
+
+/// The number of earlier submissions looked up with `--previous`
+const PREVIOUS_DISCUSSIONS: u8 = 10;
+
+/// New stories rarely show up more often than this
+const MIN_POLITE_INTERVAL: u64 = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OutputFormat {
+    Text,
+    Json,
+    JsonLines,
+    Plain,
+    Csv,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+enum ExportFormat {
+    Csv,
+    Json,
+}
+
+/// The order of the top level comments, their replies keeping the order of HN
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum CommentOrder {
+    Hn,
+    Newest,
+    Replies,
+}
 
 #[derive(Parser, Debug)]
 #[clap(
     name = "HN CLI",
     version = "1.0",
-    about = "A command line interface for Hacker News"
+    about = "A command line interface for Hacker News",
+    args_conflicts_with_subcommands = true
 )]
 struct Cli {
-    #[clap(short, long, default_value = "best")]
-    /// The type of stories to retrieve, can be 'top', 'new' or 'best'
+    #[clap(subcommand)]
+    command: Option<Command>,
+    #[clap(value_parser = parse_item_id)]
+    /// Show the comments of an item, given its id or its HN URL
+    item: Option<i64>,
+    #[clap(flatten)]
+    thread: ThreadArgs,
+    #[clap(short, long, visible_alias = "type", default_value = "best")]
+    /// The type of stories to retrieve, can be 'top', 'new', 'best', 'ask', 'show' or 'job'
     story_type: String,
-    #[clap(short, long, default_value_t=10, value_parser = clap::value_parser!(u8).range(1..=50))]
+    #[clap(short, long, default_value_t=1, value_parser = clap::value_parser!(u8).range(1..))]
+    /// The page of stories to retrieve, starting at 1
+    page: u8,
+    #[clap(short, long, visible_alias = "page-size", default_value_t=10, value_parser = clap::value_parser!(u8).range(1..=50))]
     /// The number of stories to retrieve. Should be between 1 and 50 inclusive
     length: u8,
+    #[clap(long, global = true)]
+    /// Print the results as JSON, e.g. to pipe them into jq
+    json: bool,
+    #[clap(long, global = true, conflicts_with = "json")]
+    /// Print each result as a JSON object on its own line as soon as it is fetched
+    jsonl: bool,
+    #[clap(long, global = true, conflicts_with_all = ["json", "jsonl"])]
+    /// Only print the results themselves, without numbering, separators nor footer
+    plain: bool,
+    #[clap(long, global = true, conflicts_with_all = ["json", "jsonl", "plain"])]
+    /// Print the results as CSV, e.g. to import them into a spreadsheet
+    csv: bool,
+    #[clap(long, global = true, value_name = "FILE")]
+    /// Read the configuration from this file instead of config.toml
+    config: Option<PathBuf>,
+    #[clap(long, global = true, value_name = "DIR")]
+    /// Override the directory holding the configuration
+    config_dir: Option<PathBuf>,
+    #[clap(long, global = true, value_name = "DIR")]
+    /// Override the directory holding cached data
+    cache_dir: Option<PathBuf>,
+    #[clap(long, global = true, value_parser = clap::builder::PossibleValuesParser::new(BUILTIN_THEMES))]
+    /// Use one of the bundled themes instead of theme.toml
+    theme: Option<String>,
+    #[clap(long, global = true)]
+    /// Only use bold and dim text, without colors. Also enabled by the NO_COLOR variable
+    no_color: bool,
+}
+
+impl Cli {
+    fn output_format(&self) -> OutputFormat {
+        match (self.json, self.jsonl, self.plain, self.csv) {
+            (true, _, _, _) => OutputFormat::Json,
+            (_, true, _, _) => OutputFormat::JsonLines,
+            (_, _, true, _) => OutputFormat::Plain,
+            (_, _, _, true) => OutputFormat::Csv,
+            _ => OutputFormat::Text,
+        }
+    }
+
+    /// Replaces the built-in defaults by the configured ones, the flags given explicitly win
+    fn apply_config(&mut self, matches: &ArgMatches, config: &Config) {
+        if is_default(matches, "story_type") {
+            if let Some(story_type) = &config.story_type {
+                self.story_type = story_type.clone();
+            }
+        }
+        if is_default(matches, "length") {
+            self.length = config.page_size.unwrap_or(self.length);
+        }
+        self.thread.apply_config(matches, config);
+        if self.theme.is_none() {
+            self.theme = config.theme.clone();
+        }
+        let (Some(command), Some((_, matches))) = (&mut self.command, matches.subcommand()) else {
+            return;
+        };
+        match command {
+            Command::Top(page)
+            | Command::Best(page)
+            | Command::New(page)
+            | Command::Ask(page)
+            | Command::Show(page)
+            | Command::Job(page)
+            | Command::Search { page, .. }
+            | Command::User { page, .. } => page.apply_config(matches, config),
+            Command::Pick(pick) => pick.page.apply_config(matches, config),
+            Command::Comments { thread, .. } => thread.apply_config(matches, config),
+            _ => {}
+        }
+    }
+}
+
+impl PageArgs {
+    fn apply_config(&mut self, matches: &ArgMatches, config: &Config) {
+        if is_default(matches, "limit") {
+            self.limit = config.page_size.unwrap_or(self.limit);
+        }
+    }
+}
+
+impl ThreadArgs {
+    fn apply_config(&mut self, matches: &ArgMatches, config: &Config) {
+        if is_default(matches, "depth") {
+            self.depth = config.comment_depth.unwrap_or(self.depth);
+        }
+        if !self.no_show_dead {
+            self.show_dead |= config.show_dead.unwrap_or_default();
+        }
+        if !self.no_hide_deleted {
+            self.hide_deleted |= config.hide_deleted.unwrap_or_default();
+        }
+        self.collapse = config.collapse.clone();
+        self.tts_command = config.tts_command.clone();
+    }
+
+    fn window(&self) -> CommentWindow {
+        CommentWindow {
+            skip: self.skip,
+            limit: self.per_level.map(usize::from),
+            after: None,
+        }
+    }
+}
+
+fn is_default(matches: &ArgMatches, id: &str) -> bool {
+    matches.value_source(id) == Some(ValueSource::DefaultValue)
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List the top stories
+    Top(PageArgs),
+    /// List the best stories
+    Best(PageArgs),
+    /// List the newest stories
+    New(PageArgs),
+    /// List the Ask HN stories
+    Ask(PageArgs),
+    /// List the Show HN stories
+    Show(PageArgs),
+    /// List the job offers
+    Job(PageArgs),
+    /// Show the comments of an item
+    Comments {
+        #[clap(value_parser = parse_item_id)]
+        /// The id or HN URL of the item
+        item: i64,
+        #[clap(flatten)]
+        thread: ThreadArgs,
+    },
+    /// Print the raw API record of an item, e.g. for bug reports
+    Raw {
+        #[clap(value_parser = parse_item_id)]
+        /// The id or HN URL of the item
+        item: i64,
+    },
+    /// Print an item with its parents and its number of descendants
+    Inspect {
+        #[clap(value_parser = parse_item_id)]
+        /// The id or HN URL of the item
+        item: i64,
+    },
+    /// Print the URL of the HN page of a comment, e.g. `xdg-open $(hn permalink 42)` to share
+    /// it rather than the whole story
+    Permalink {
+        #[clap(value_parser = parse_item_id)]
+        /// The id or HN URL of the comment
+        item: i64,
+    },
+    /// Print the text of a comment without styles, e.g. `hn text 42 | xclip -selection
+    /// clipboard` to copy it
+    Text {
+        #[clap(value_parser = parse_item_id)]
+        /// The id or HN URL of the comment
+        item: i64,
+        #[clap(long)]
+        /// End the text with the URL of the comment, to share it with its source
+        permalink: bool,
+    },
+    /// Print the target of a link of a comment, e.g. `xdg-open $(hn link 42 2)`, picking it
+    /// from the list of links when no number is given
+    Link {
+        #[clap(value_parser = parse_item_id)]
+        /// The id or HN URL of the comment
+        item: i64,
+        #[clap(value_parser = clap::value_parser!(u8).range(1..))]
+        /// The number of the link, as shown by its "[N]" marker
+        number: Option<u8>,
+    },
+    /// List the links of the text and comments of a thread, e.g. `hn links 42 --pick | xargs
+    /// -n1 xdg-open`
+    Links {
+        #[clap(value_parser = parse_item_id)]
+        /// The id or HN URL of the item
+        item: i64,
+        #[clap(short, long, default_value_t = 3)]
+        /// The number of comment levels to retrieve
+        depth: u8,
+        #[clap(long)]
+        /// Pick some of the links from the list and only print them
+        pick: bool,
+    },
+    /// Search stories matching a query
+    Search {
+        query: String,
+        #[clap(flatten)]
+        page: PageArgs,
+    },
+    /// List previous HN submissions of a URL
+    Past {
+        url: String,
+        #[clap(short, long, default_value_t=10, value_parser = clap::value_parser!(u8).range(1..=50))]
+        /// The number of submissions to retrieve. Should be between 1 and 50 inclusive
+        limit: u8,
+    },
+    /// List the other submissions of a story, of the same URL or with a similar title
+    Similar {
+        #[clap(value_parser = parse_item_id)]
+        /// The id or HN URL of the story
+        item: i64,
+        #[clap(short, long, default_value_t=10, value_parser = clap::value_parser!(u8).range(1..=50))]
+        /// The number of submissions to retrieve. Should be between 1 and 50 inclusive
+        limit: u8,
+    },
+    /// List the latest stories and comments submitted by a user, or their favorites
+    User {
+        user: String,
+        #[clap(flatten)]
+        page: PageArgs,
+        #[clap(long)]
+        /// List the public favorites of the user instead
+        favorites: bool,
+    },
+    /// Extract the job listings of a "Who is hiring?" thread
+    Hiring(HiringArgs),
+    /// Pick a story from a list and print its URL, e.g. `xdg-open $(hn pick)`
+    Pick(PickArgs),
+    /// Print the directories used for the configuration, cache, data and logs
+    Paths,
+    /// Poll a story type and print the stories that were not seen before
+    Watch(WatchArgs),
+    /// List the terms shared by the most titles, or the stories of a term (experimental)
+    Topics(TopicsArgs),
+    /// Bookmark a comment or a story, to find it again with `bookmarks`
+    Bookmark {
+        #[clap(value_parser = parse_item_id)]
+        /// The id or HN URL of the item
+        item: i64,
+    },
+    /// List the bookmarked comments and stories
+    Bookmarks {
+        #[clap(long, value_name = "ITEM", value_parser = parse_item_id)]
+        /// Remove the bookmark of this item instead
+        remove: Option<i64>,
+    },
+    /// List what was noticed while running, e.g. the new stories found by `watch`
+    Activity {
+        #[clap(short = 'n', long, default_value_t = 20)]
+        /// The number of events to list, the latest ones
+        limit: usize,
+        #[clap(long)]
+        /// Forget all the events instead
+        clear: bool,
+    },
+}
+
+#[derive(Args, Debug)]
+struct PageArgs {
+    #[clap(short, long, default_value_t=1, value_parser = clap::value_parser!(u8).range(1..))]
+    /// The page to retrieve, starting at 1
+    page: u8,
+    #[clap(short, long, default_value_t=10, value_parser = clap::value_parser!(u8).range(1..=50))]
+    /// The number of results per page. Should be between 1 and 50 inclusive
+    limit: u8,
+    #[clap(long)]
+    /// Only keep the Launch HN posts of YC companies
+    launch_only: bool,
+    #[clap(long, conflicts_with = "jsonl")]
+    /// Group the stories by the domain of their URL in the text output
+    by_domain: bool,
+    #[clap(long, conflicts_with = "jsonl")]
+    /// Sort the stories by points per hour, e.g. to spot the new ones about to reach the front page
+    by_velocity: bool,
+}
+
+/// How the comments of an item are retrieved and shown
+#[derive(Args, Debug)]
+#[group(requires = "item")]
+struct ThreadArgs {
+    #[clap(short, long, default_value_t = 3)]
+    /// The number of comment levels to retrieve
+    depth: u8,
+    #[clap(long, default_value_t = 60, conflicts_with_all = ["json", "jsonl"])]
+    /// Cut the comments longer than this number of lines, 0 to never cut them
+    max_lines: usize,
+    #[clap(long, conflicts_with = "jsonl")]
+    /// Look up the earlier submissions of the story URL
+    previous: bool,
+    #[clap(long, overrides_with = "no_show_dead")]
+    /// Show the text of the comments killed by moderators or flags, dimmed
+    show_dead: bool,
+    #[clap(long, overrides_with = "show_dead")]
+    /// Hide the text of the dead comments even if show_dead is set in the configuration
+    no_show_dead: bool,
+    #[clap(long, conflicts_with = "jsonl", overrides_with = "no_hide_deleted")]
+    /// Leave out the deleted comments that have no reply
+    hide_deleted: bool,
+    #[clap(long, overrides_with = "hide_deleted")]
+    /// Keep the deleted comments even if hide_deleted is set in the configuration
+    no_hide_deleted: bool,
+    #[clap(long, conflicts_with = "jsonl")]
+    /// Show the karma and account age of the comment authors
+    flair: bool,
+    #[clap(long, conflicts_with = "depth")]
+    /// Fetch every level of replies however deep, instead of --depth levels
+    expand_all: bool,
+    #[clap(long, value_parser = clap::value_parser!(u16).range(1..))]
+    /// Fetch at most this number of comments per level, e.g. in threads of hundreds of comments
+    per_level: Option<u16>,
+    #[clap(long, default_value_t = 0)]
+    /// Skip this number of top level comments, to fetch the ones after --per-level
+    skip: usize,
+    #[clap(long, conflicts_with_all = ["skip", "jsonl"])]
+    /// Continue after the last top level comment shown by the previous --resume of the thread,
+    /// e.g. to read it --per-level comments at a time
+    resume: bool,
+    #[clap(long, value_name = "PATTERN", conflicts_with = "jsonl")]
+    /// Only show the comments containing this text outside of code blocks or written by this
+    /// author, and the ones they reply to, the matches highlighted
+    grep: Option<String>,
+    #[clap(long, value_name = "USERNAME", conflicts_with = "jsonl")]
+    /// Only show the comments written by this user, and the ones they reply to
+    author: Option<String>,
+    #[clap(long, value_enum, default_value_t = CommentOrder::Hn, conflicts_with = "jsonl")]
+    /// The order of the top level comments: the ranking of HN, the newest or the most replied first
+    sort: CommentOrder,
+    #[clap(long, conflicts_with_all = ["sort", "jsonl"])]
+    /// List the comments by date with the author they reply to, instead of as an indented tree
+    flat: bool,
+    #[clap(long, conflicts_with_all = ["json", "jsonl"])]
+    /// Read the story and its top level comments aloud with the tts_command of the configuration
+    speak: bool,
+    #[clap(skip)]
+    collapse: CollapseRules,
+    #[clap(skip)]
+    tts_command: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct HiringArgs {
+    /// The id of the thread, the latest one if not given
+    thread_id: Option<i64>,
+    #[clap(long)]
+    /// Only keep the job listings mentioning remote work
+    remote_only: bool,
+    #[clap(long)]
+    /// Only keep the job listings offering onsite work
+    onsite_only: bool,
+    #[clap(long)]
+    /// Only keep the job listings mentioning visas
+    visa: bool,
+    #[clap(long)]
+    /// Only keep the job listings whose header contains the keyword
+    keyword: Option<String>,
+    #[clap(long, value_enum)]
+    /// Export the job listings instead of printing them
+    export: Option<ExportFormat>,
+}
+
+#[derive(Args, Debug)]
+struct PickArgs {
+    #[clap(short, long, default_value = "best")]
+    /// The type of stories to pick from, can be 'top', 'new', 'best', 'ask', 'show' or 'job'
+    story_type: String,
+    #[clap(flatten)]
+    page: PageArgs,
+    #[clap(long)]
+    /// Print the id of the picked story instead of its URL
+    id: bool,
+    #[clap(long, conflicts_with = "id")]
+    /// Print the URL of the HN comments of the picked story instead of its URL
+    comments: bool,
+    #[clap(long)]
+    /// Pick from the numbered list even when fzf is installed
+    no_fzf: bool,
+}
+
+#[derive(Args, Debug)]
+struct WatchArgs {
+    #[clap(short = 't', long = "type", default_value = "new")]
+    /// The type of stories to watch, can be 'top', 'new', 'best', 'ask', 'show' or 'job'
+    story_type: String,
+    #[clap(short, long, default_value_t=60, value_parser = clap::value_parser!(u64).range(1..))]
+    /// The number of seconds between two polls
+    interval: u64,
+    #[clap(short, long, default_value_t=30, value_parser = clap::value_parser!(u8).range(1..=50))]
+    /// The number of stories to check at each poll. Should be between 1 and 50 inclusive
+    limit: u8,
+    #[clap(long)]
+    /// Read commands on stdin, one per line: refresh, type <TYPE>, json or quit
+    stdin: bool,
+}
+
+#[derive(Args, Debug)]
+struct TopicsArgs {
+    /// Only list the stories whose title uses this term
+    term: Option<String>,
+    #[clap(short = 't', long = "type", default_value = "top")]
+    /// The type of stories to look at, can be 'top', 'new', 'best', 'ask', 'show' or 'job'
+    story_type: String,
+    #[clap(long, default_value_t=2, value_parser = clap::value_parser!(u8).range(1..=5))]
+    /// The number of pages of 30 stories to look at. Should be between 1 and 5 inclusive
+    pages: u8,
+    #[clap(short = 'n', long, default_value_t=10, value_parser = clap::value_parser!(u8).range(1..=50))]
+    /// The number of terms to list. Should be between 1 and 50 inclusive
+    count: u8,
+}
+
+fn parse_item_id(arg: &str) -> Result<i64> {
+    let id = match arg.split_once("id=") {
+        Some((_, query)) => query.split('&').next().unwrap_or_default(),
+        None => arg,
+    };
+    id.parse()
+        .map_err(|_| anyhow::anyhow!("Not an item id or HN item URL: {}", arg))
 }
 
 fn validate_args(args: &Cli, valid_story_types: HashSet<&'static str>) -> Result<()> {
-    match valid_story_types.contains(&args.story_type.as_str()) {
+    let story_type = match &args.command {
+        Some(Command::Pick(pick)) => &pick.story_type,
+        Some(Command::Watch(watch)) => &watch.story_type,
+        Some(Command::Topics(topics)) => &topics.story_type,
+        _ => &args.story_type,
+    };
+    match valid_story_types.contains(&story_type.as_str()) {
         true => Ok(()),
-        false => Err(anyhow::anyhow!("Invalid story type: {}", args.story_type)),
+        false => Err(anyhow::anyhow!("Invalid story type: {}", story_type)),
+    }
+}
+
+fn print_json_line(value: &impl Serialize) {
+    match serde_json::to_string(value) {
+        Ok(line) => println!("{}", line),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
+
+/// The stories of a page that are listed, in the order they are listed
+fn listed_items<'a>(items: &'a [HNCLIItem], page: &PageArgs) -> Result<Vec<&'a HNCLIItem>> {
+    let mut items: Vec<_> = items
+        .iter()
+        .filter(|item| !page.launch_only || item.is_launch)
+        .collect();
+    if page.by_velocity {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        items.sort_by(|a, b| b.velocity(now).total_cmp(&a.velocity(now)));
+    }
+    Ok(items)
+}
+
+fn print_items(
+    items: &[HNCLIItem],
+    page: &PageArgs,
+    footer: &str,
+    show_age_buckets: bool,
+    format: OutputFormat,
+    theme: &Theme,
+) -> Result<()> {
+    let items = listed_items(items, page)?;
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&items)?);
+            return Ok(());
+        }
+        OutputFormat::JsonLines => {
+            items.iter().for_each(print_json_line);
+            return Ok(());
+        }
+        OutputFormat::Plain => {
+            items.iter().for_each(|item| println!("{}\n", item));
+            return Ok(());
+        }
+        OutputFormat::Csv => {
+            print!("{}", items_to_csv(items));
+            return Ok(());
+        }
+        OutputFormat::Text => {}
     }
+    if page.by_domain {
+        for (domain, positions) in group_by_domain(&items) {
+            println!("\n— {} ({}) —", domain, positions.len());
+            for idx in positions {
+                println!("\n#{} {}", idx + 1, Themed(items[idx], theme));
+            }
+        }
+        print!("\n^ {} ^\n", footer);
+        return Ok(());
+    }
+    let mut current_bucket = None;
+    for (idx, item) in items.iter().enumerate() {
+        // the buckets only make sense while the list is chronological
+        if show_age_buckets && !page.by_velocity {
+            let bucket = item.age_bucket();
+            if current_bucket.as_ref() != Some(&bucket) {
+                println!("\n— {} —", bucket);
+                current_bucket = Some(bucket);
+            }
+        }
+        println!("\n#{} {}", idx + 1, Themed(*item, theme));
+    }
+    print!("\n^ {} ^\n", footer);
+    Ok(())
 }
 
-async fn run(args: Cli, service: &impl HackerNewsCliService) -> Result<()> {
+async fn run_stories(
+    story_type: &str,
+    page: &PageArgs,
+    format: OutputFormat,
+    theme: &Theme,
+    service: &impl HackerNewsCliService,
+) -> Result<()> {
+    if format == OutputFormat::JsonLines {
+        let print_item = |item: HNCLIItem| {
+            if !page.launch_only || item.is_launch {
+                print_json_line(&item);
+            }
+        };
+        return service
+            .stream_top_n_stories(story_type, page.page, page.limit, &print_item)
+            .await;
+    }
     let items = service
-        .fetch_top_n_stories(&args.story_type, args.length)
+        .fetch_top_n_stories(story_type, page.page, page.limit)
         .await?;
-    for (idx, item) in items.iter().enumerate() {
-        println!("\n#{} {}", idx + 1, item);
+    let footer = match page.page {
+        1 => format!("Enjoy the top {} {} HN stories!", page.limit, story_type),
+        n => format!("Enjoy page {} of the top {} HN stories!", n, story_type),
+    };
+    // the new feed is chronological, so separate it by age to show how far back it goes
+    print_items(&items, page, &footer, story_type == "new", format, theme)
+}
+
+async fn run_hiring(
+    args: &HiringArgs,
+    format: OutputFormat,
+    service: &impl HackerNewsCliService,
+) -> Result<()> {
+    let thread = service.fetch_hiring_thread(args.thread_id).await?;
+    let keyword = args.keyword.as_ref().map(|keyword| keyword.to_lowercase());
+    let listings: Vec<_> = thread
+        .listings
+        .into_iter()
+        .filter(|listing| !args.remote_only || listing.remote)
+        .filter(|listing| !args.onsite_only || listing.onsite)
+        .filter(|listing| !args.visa || listing.visa)
+        .filter(|listing| match &keyword {
+            Some(keyword) => listing.header.to_lowercase().contains(keyword),
+            None => true,
+        })
+        .collect();
+    let export = match format {
+        OutputFormat::Json => Some(ExportFormat::Json),
+        OutputFormat::Csv => Some(ExportFormat::Csv),
+        OutputFormat::JsonLines => {
+            listings.iter().for_each(print_json_line);
+            return Ok(());
+        }
+        OutputFormat::Plain => {
+            listings
+                .iter()
+                .for_each(|listing| println!("{}\n", listing));
+            return Ok(());
+        }
+        OutputFormat::Text => args.export.clone(),
+    };
+    match export {
+        Some(ExportFormat::Csv) => print!("{}", listings_to_csv(&listings)),
+        Some(ExportFormat::Json) => println!("{}", serde_json::to_string_pretty(&listings)?),
+        None => {
+            for (idx, listing) in listings.iter().enumerate() {
+                println!("\n#{} {}", idx + 1, listing);
+            }
+            print!(
+                "\n^ {} job listings from '{}' ^\n",
+                listings.len(),
+                thread.story.title
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Asks on stderr for a number between 1 and `count`, returning the index it stands for
+fn prompt_pick(what: &str, count: usize) -> Result<usize> {
+    eprint!("Pick a {} [1-{}]: ", what, count);
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    answer
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|idx| (1..=count).contains(idx))
+        .map(|idx| idx - 1)
+        .ok_or_else(|| anyhow::anyhow!("Invalid pick: {}", answer.trim()))
+}
+
+/// Asks on stderr for numbers between 1 and `count`, e.g. "1 3 4" or "all", returning the
+/// indexes they stand for
+fn prompt_picks(what: &str, count: usize) -> Result<Vec<usize>> {
+    eprint!("Pick {} [1-{}, all]: ", what, count);
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    parse_picks(&answer, count).ok_or_else(|| anyhow::anyhow!("Invalid pick: {}", answer.trim()))
+}
+
+fn parse_picks(answer: &str, count: usize) -> Option<Vec<usize>> {
+    if answer.trim() == "all" {
+        return Some((0..count).collect());
+    }
+    let picks = answer
+        .split([' ', ','])
+        .filter(|pick| !pick.trim().is_empty())
+        .map(|pick| {
+            pick.trim()
+                .parse::<usize>()
+                .ok()
+                .filter(|idx| (1..=count).contains(idx))
+                .map(|idx| idx - 1)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(picks).filter(|picks| !picks.is_empty())
+}
+
+async fn run_pick(args: &PickArgs, service: &impl HackerNewsCliService) -> Result<()> {
+    let stories = service
+        .fetch_top_n_stories(&args.story_type, args.page.page, args.page.limit)
+        .await?;
+    let groups = pick_groups(&listed_items(&stories, &args.page)?, args.page.by_domain);
+    let items: Vec<&HNCLIItem> = (groups.iter())
+        .flat_map(|(_, group)| group.iter().copied())
+        .collect();
+    let lines: Vec<String> = (items.iter().enumerate())
+        .map(|(idx, item)| fzf_line(idx, item))
+        .collect();
+    let picked = match args.no_fzf {
+        true => None,
+        false => fzf_pick(&lines)?,
+    };
+    let idx = match picked {
+        Some(idx) => idx,
+        None => {
+            // the list and the prompt go to stderr so that only the pick ends up on stdout
+            let mut number = 0;
+            for (domain, group) in &groups {
+                if args.page.by_domain {
+                    eprintln!("\n— {} ({}) —", domain, group.len());
+                }
+                for item in group {
+                    number += 1;
+                    eprintln!("\n#{} {}", number, item);
+                }
+            }
+            eprintln!();
+            prompt_pick("story", items.len())?
+        }
+    };
+    let item = items[idx];
+    match (args.id, args.comments) {
+        (true, _) => println!("{}", item.id),
+        (_, true) => println!("{}", service.permalink(item.id)),
+        _ => println!("{}", item.url),
+    }
+    Ok(())
+}
+
+/// The stories to pick from, grouped by domain with `by_domain` and else in a single group,
+/// numbered in that order
+fn pick_groups<'a>(items: &[&'a HNCLIItem], by_domain: bool) -> Vec<(&'a str, Vec<&'a HNCLIItem>)> {
+    if !by_domain {
+        return vec![("", items.to_vec())];
+    }
+    group_by_domain(items)
+        .into_iter()
+        .map(|(domain, positions)| (domain, positions.iter().map(|idx| items[*idx]).collect()))
+        .collect()
+}
+
+/// A story on a single line for fzf, its index hidden in a first field
+fn fzf_line(idx: usize, item: &HNCLIItem) -> String {
+    format!(
+        "{}\t{} [{} points, {} comments, {}] {}",
+        idx,
+        item.title,
+        item.score.unwrap_or_default(),
+        item.comments.unwrap_or_default(),
+        item.time_ago,
+        item.url
+    )
+}
+
+/// Lets fzf pick one of the lines, `None` meaning that fzf is not installed
+fn fzf_pick(lines: &[String]) -> Result<Option<usize>> {
+    // fzf draws on the terminal itself, only the list and the pick go through the pipes
+    let child = std::process::Command::new("fzf")
+        .args(["--delimiter", "\t", "--with-nth", "2..", "--no-sort"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context("Could not run fzf"),
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(lines.join("\n").as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!("No story picked");
+    }
+    let line = String::from_utf8_lossy(&output.stdout);
+    parse_fzf_pick(&line, lines.len())
+        .map(Some)
+        .ok_or_else(|| anyhow::anyhow!("Invalid pick: {}", line.trim()))
+}
+
+fn parse_fzf_pick(line: &str, count: usize) -> Option<usize> {
+    let (idx, _) = line.split_once('\t')?;
+    idx.parse().ok().filter(|idx| *idx < count)
+}
+
+async fn run_topics(
+    args: &TopicsArgs,
+    format: OutputFormat,
+    theme: &Theme,
+    service: &impl HackerNewsCliService,
+) -> Result<()> {
+    let mut items = Vec::new();
+    for page in 1..=args.pages {
+        items.extend(
+            service
+                .fetch_top_n_stories(&args.story_type, page, TOPICS_PAGE_SIZE)
+                .await?,
+        );
+    }
+    if let Some(term) = &args.term {
+        let term = term.to_lowercase();
+        items.retain(|item| topic_terms(&item.title).contains(&term));
+        let footer = format!(
+            "{} {} HN stories about {}",
+            items.len(),
+            args.story_type,
+            term
+        );
+        let page = PageArgs {
+            page: 1,
+            limit: TOPICS_PAGE_SIZE,
+            launch_only: false,
+            by_domain: false,
+            by_velocity: false,
+        };
+        return print_items(&items, &page, &footer, false, format, theme);
+    }
+    let topics = extract_topics(
+        items.iter().map(|item| item.title.as_str()),
+        args.count.into(),
+    );
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&topics)?),
+        OutputFormat::JsonLines => topics.iter().for_each(print_json_line),
+        _ => topics.iter().for_each(|topic| println!("{}", topic)),
+    }
+    Ok(())
+}
+
+/// A line read by `watch --stdin`
+#[derive(Debug, PartialEq)]
+enum WatchCommand {
+    /// Poll now instead of waiting for the interval
+    Refresh,
+    /// Watch another story type from now on
+    Type(String),
+    /// Print the stories of the last poll as a JSON array on a single line
+    Json,
+    Quit,
+}
+
+fn parse_watch_command(line: &str) -> Result<WatchCommand> {
+    let words: Vec<_> = line.split_whitespace().collect();
+    let valid_story_types = HackerNewsCliServiceImpl::get_valid_story_types();
+    Ok(match words[..] {
+        ["refresh"] => WatchCommand::Refresh,
+        ["type", story_type] if valid_story_types.contains(story_type) => {
+            WatchCommand::Type(story_type.to_string())
+        }
+        ["json"] => WatchCommand::Json,
+        ["quit"] => WatchCommand::Quit,
+        _ => bail!(
+            "Unknown command: {}, should be refresh, type <TYPE>, json or quit",
+            line.trim()
+        ),
+    })
+}
+
+/// The next line of stdin, or never when it is not read
+async fn next_line(lines: &mut Option<Lines<BufReader<Stdin>>>) -> Option<String> {
+    match lines {
+        Some(lines) => lines.next_line().await.ok().flatten(),
+        None => std::future::pending().await,
+    }
+}
+
+/// The ids already printed for a story type, starting afresh when the file is corrupted
+fn load_seen(paths: &Paths, story_type: &str) -> Result<SeenIds> {
+    let path = paths.data_dir.join(format!("seen-{}.json", story_type));
+    let seen = SeenIds::load(&path)?;
+    warn_corrupt(
+        &path,
+        seen.corrupt_backup(),
+        "all stories are considered unseen",
+    );
+    Ok(seen)
+}
+
+fn load_activity(paths: &Paths) -> Result<ActivityLog> {
+    let path = paths.data_dir.join("activity.json");
+    let activity = ActivityLog::load(&path)?;
+    warn_corrupt(
+        &path,
+        activity.corrupt_backup(),
+        "the activity log starts afresh",
+    );
+    Ok(activity)
+}
+
+fn load_bookmarks(paths: &Paths) -> Result<Bookmarks> {
+    let path = paths.data_dir.join("bookmarks.json");
+    let bookmarks = Bookmarks::load(&path)?;
+    warn_corrupt(
+        &path,
+        bookmarks.corrupt_backup(),
+        "the bookmarks start afresh",
+    );
+    Ok(bookmarks)
+}
+
+/// Tells where a file that could not be parsed was moved, and what starting afresh means
+fn warn_corrupt(path: &Path, backup: Option<&Path>, consequence: &str) {
+    if let Some(backup) = backup {
+        eprintln!(
+            "Warning: `{}` was corrupted and moved to `{}`, {}",
+            path.display(),
+            backup.display(),
+            consequence
+        );
+    }
+}
+
+async fn run_watch(
+    args: &WatchArgs,
+    paths: &Paths,
+    format: OutputFormat,
+    theme: &Theme,
+    service: &impl HackerNewsCliService,
+) -> Result<()> {
+    let mut story_type = args.story_type.clone();
+    let mut seen = load_seen(paths, &story_type)?;
+    let mut activity = load_activity(paths)?;
+    if args.interval < MIN_POLITE_INTERVAL {
+        eprintln!(
+            "Warning: polling every {} seconds puts needless load on the HN API, \
+            consider an interval of at least {} seconds",
+            args.interval, MIN_POLITE_INTERVAL
+        );
+    }
+    let mut interval = tokio::time::interval(Duration::from_secs(args.interval));
+    let mut commands = args
+        .stdin
+        .then(|| BufReader::new(tokio::io::stdin()).lines());
+    let mut last_items = Vec::new();
+    if format == OutputFormat::Csv {
+        print!("{}", items_to_csv([]));
+    }
+    loop {
+        let line = tokio::select! {
+            _ = interval.tick() => None,
+            line = next_line(&mut commands) => Some(line),
+        };
+        match line.map(|line| line.map(|line| parse_watch_command(&line))) {
+            None => {}
+            // stdin was closed, e.g. by the tool driving the watch, which goes on by itself
+            Some(None) => {
+                commands = None;
+                continue;
+            }
+            Some(Some(Ok(WatchCommand::Refresh))) => interval.reset(),
+            Some(Some(Ok(WatchCommand::Type(new_story_type)))) => {
+                seen = load_seen(paths, &new_story_type)?;
+                story_type = new_story_type;
+                interval.reset();
+            }
+            Some(Some(Ok(WatchCommand::Json))) => {
+                println!("{}", serde_json::to_string(&last_items)?);
+                continue;
+            }
+            Some(Some(Ok(WatchCommand::Quit))) => return Ok(()),
+            Some(Some(Err(e))) => {
+                eprintln!("Error: {}", e);
+                continue;
+            }
+        }
+        let items = match service
+            .fetch_top_n_stories(&story_type, 1, args.limit)
+            .await
+        {
+            Ok(items) => items,
+            Err(e) => {
+                // a failed poll should not stop the watch, the next one may succeed
+                eprintln!("Error: {}", e);
+                continue;
+            }
+        };
+        let ids: Vec<i64> = items.iter().map(|item| item.id).collect();
+        let unseen = seen.mark_seen(&ids);
+        let unseen_items = items.iter().filter(|item| unseen.contains(&item.id));
+        for item in unseen_items {
+            activity.record(ActivityEvent {
+                timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+                message: format!("new on {}: {}", story_type, item.title),
+                item: Some(item.id),
+            });
+            match format {
+                OutputFormat::Json | OutputFormat::JsonLines => print_json_line(item),
+                OutputFormat::Csv => print!(
+                    "{}",
+                    items_to_csv([item]).split_once('\n').unwrap_or_default().1
+                ),
+                OutputFormat::Text => println!("{}\n", Themed(item, theme)),
+                OutputFormat::Plain => println!("{}\n", item),
+            }
+        }
+        seen.save()?;
+        activity.save()?;
+        last_items = items;
+    }
+}
+
+/// Fetches all the comments of an item, counting them on stderr as they come
+async fn fetch_full_thread(
+    id: i64,
+    window: CommentWindow,
+    service: &impl HackerNewsCliService,
+) -> Result<HNCLIThread> {
+    let stderr = Term::stderr();
+    let fetched = AtomicUsize::new(0);
+    let on_progress = |count: usize| {
+        let fetched = fetched.fetch_add(count, Ordering::Relaxed) + count;
+        if stderr.is_term() {
+            eprint!("\rFetched {} comments", fetched);
+        }
+    };
+    let thread = service.fetch_full_thread(id, window, &on_progress).await;
+    if stderr.is_term() {
+        stderr.clear_line()?;
+    }
+    thread
+}
+
+/// Looks up the flairs of the comment authors, those fetched during the last day being cached
+async fn fetch_flairs(
+    thread: &HNCLIThread,
+    paths: &Paths,
+    service: &impl HackerNewsCliService,
+) -> Result<HashMap<String, UserFlair>> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let mut cache = FlairCache::load(&paths.cache_dir.join("users.json"))?;
+    let mut authors: Vec<&str> = Vec::new();
+    for comment in &thread.comments {
+        for author in comment.authors() {
+            if !authors.contains(&author) {
+                authors.push(author);
+            }
+        }
+    }
+    let mut flairs: HashMap<String, UserFlair> = authors
+        .iter()
+        .filter_map(|author| Some((author.to_string(), cache.get(author, now)?)))
+        .collect();
+    let missing: Vec<&str> = authors
+        .into_iter()
+        .filter(|author| !flairs.contains_key(*author))
+        .collect();
+    for (author, flair) in service.fetch_user_flairs(&missing).await {
+        cache.insert(&author, flair, now);
+        flairs.insert(author, flair);
     }
-    print!(
-        "\n^ Enjoy the top {} {} HN stories! ^\n",
-        args.length, args.story_type
+    cache.save(now)?;
+    Ok(flairs)
+}
+
+async fn run_comments(
+    id: i64,
+    args: &ThreadArgs,
+    paths: &Paths,
+    format: OutputFormat,
+    theme: &Theme,
+    service: &impl HackerNewsCliService,
+) -> Result<()> {
+    if format == OutputFormat::JsonLines {
+        let print_entry = |entry: ThreadEntry| print_json_line(&entry);
+        let depth = match args.expand_all {
+            true => u8::MAX,
+            false => args.depth,
+        };
+        return service
+            .stream_thread(id, depth, args.window(), args.show_dead, &print_entry)
+            .await;
+    }
+    let progress_path = paths.data_dir.join("progress.json");
+    let mut progress = ReadingProgress::load(&progress_path)?;
+    warn_corrupt(
+        &progress_path,
+        progress.corrupt_backup(),
+        "the reading progress starts afresh",
     );
+    let mut window = args.window();
+    match (args.resume, progress.last_read(id)) {
+        (true, last_read) => window.after = last_read,
+        (false, Some(_)) => eprintln!("Read before, --resume continues where you left off"),
+        (false, None) => {}
+    }
+    let visited = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let mut thread = match args.expand_all {
+        true => fetch_full_thread(id, window, service).await?,
+        false => service.fetch_thread(id, args.depth, window).await?,
+    };
+    if args.resume {
+        match thread.comments.last() {
+            Some(last) => {
+                progress.mark_read(id, last.id);
+                progress.save()?;
+            }
+            None => eprintln!("No comments left after the last one read"),
+        }
+    }
+    if !args.show_dead {
+        for comment in &mut thread.comments {
+            comment.hide_dead();
+        }
+    }
+    if args.hide_deleted {
+        prune_deleted(&mut thread.comments);
+    }
+    for comment in &mut thread.comments {
+        comment.collapse(&args.collapse);
+    }
+    match args.sort {
+        CommentOrder::Hn => {}
+        CommentOrder::Newest => thread
+            .comments
+            .sort_by_key(|comment| std::cmp::Reverse(comment.timestamp)),
+        CommentOrder::Replies => thread
+            .comments
+            .sort_by_key(|comment| std::cmp::Reverse(comment.reply_count)),
+    }
+    if let Some(author) = &args.author {
+        let count = filter_by_author(&mut thread.comments, author);
+        eprintln!("{} comments by {}", count, author);
+    }
+    if let Some(pattern) = &args.grep {
+        let count = grep_comments(&mut thread.comments, pattern);
+        eprintln!("{} matching comments", count);
+    }
+    if args.flair {
+        thread.flairs = fetch_flairs(&thread, paths, service).await?;
+    }
+    if args.previous {
+        thread.previous = service
+            .fetch_previous_discussions(&thread.story, PREVIOUS_DISCUSSIONS)
+            .await?;
+    }
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&thread)?);
+        return Ok(());
+    }
+    thread.new_since = progress.last_visit(id);
+    if thread.new_since.is_some() {
+        eprintln!("{} new comments since the last visit", thread.new_count());
+    }
+    // only wrapped when printed to a terminal, the lines are left alone when piped
+    thread.flat = args.flat;
+    thread.resumed = args.resume;
+    if let Some((_, columns)) = Term::stdout().size_checked() {
+        thread.wrap(columns as usize);
+    }
+    if args.max_lines > 0 {
+        for comment in &mut thread.comments {
+            comment.truncate_lines(args.max_lines);
+        }
+    }
+    match format {
+        OutputFormat::Text => println!("{}", Themed(&thread, theme)),
+        _ => println!("{}", thread),
+    }
+    progress.mark_visited(id, visited);
+    progress.save()?;
+    if args.speak {
+        speak(&thread.spoken_text(), args.tts_command.as_deref())?;
+    }
+    Ok(())
+}
+
+/// Writes the text to the standard input of the text-to-speech command, waiting for it to
+/// finish reading
+fn speak(text: &str, tts_command: Option<&str>) -> Result<()> {
+    let tts_command = tts_command
+        .context("No tts_command configured, set e.g. tts_command = \"espeak\" in config.toml")?;
+    let mut words = tts_command.split_whitespace();
+    let program = words.next().context("Empty tts_command")?;
+    let mut child = std::process::Command::new(program)
+        .args(words)
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Could not run `{}`", tts_command))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("`{}` failed: {}", tts_command, status);
+    }
     Ok(())
 }
 
+async fn run(
+    args: Cli,
+    paths: &Paths,
+    theme: &Theme,
+    service: &impl HackerNewsCliService,
+) -> Result<()> {
+    let format = args.output_format();
+    let command = match args.command {
+        Some(command) => command,
+        None => {
+            if let Some(id) = args.item {
+                return run_comments(id, &args.thread, paths, format, theme, service).await;
+            }
+            let page = PageArgs {
+                page: args.page,
+                limit: args.length,
+                launch_only: false,
+                by_domain: false,
+                by_velocity: false,
+            };
+            return run_stories(&args.story_type, &page, format, theme, service).await;
+        }
+    };
+    match command {
+        Command::Top(page) => run_stories("top", &page, format, theme, service).await,
+        Command::Best(page) => run_stories("best", &page, format, theme, service).await,
+        Command::New(page) => run_stories("new", &page, format, theme, service).await,
+        Command::Ask(page) => run_stories("ask", &page, format, theme, service).await,
+        Command::Show(page) => run_stories("show", &page, format, theme, service).await,
+        Command::Job(page) => run_stories("job", &page, format, theme, service).await,
+        Command::Comments { item, thread } => {
+            run_comments(item, &thread, paths, format, theme, service).await
+        }
+        Command::Raw { item } => {
+            let item = service.fetch_raw_item(item).await?;
+            match format {
+                OutputFormat::JsonLines => print_json_line(&item),
+                _ => println!("{}", serde_json::to_string_pretty(&item)?),
+            }
+            Ok(())
+        }
+        Command::Inspect { item } => {
+            let inspection = service.inspect_item(item).await?;
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&inspection)?),
+                OutputFormat::JsonLines => print_json_line(&inspection),
+                _ => println!("{}", inspection),
+            }
+            Ok(())
+        }
+        Command::Permalink { item } => {
+            if service.fetch_raw_item(item).await?.is_null() {
+                bail!("Item {} does not exist", item);
+            }
+            println!("{}", service.permalink(item));
+            Ok(())
+        }
+        Command::Text { item, permalink } => {
+            let text = service.fetch_item_text(item).await?;
+            if text.is_empty() {
+                bail!("Item {} has no text", item);
+            }
+            println!("{}", text);
+            if permalink {
+                println!("\n{}", service.permalink(item));
+            }
+            Ok(())
+        }
+        Command::Link { item, number } => {
+            let links = service.fetch_item_links(item).await?;
+            if links.is_empty() {
+                bail!("Item {} has no links", item);
+            }
+            let link = match number {
+                Some(number) => links.get(usize::from(number) - 1).with_context(|| {
+                    format!("Item {} has {} links, not {}", item, links.len(), number)
+                })?,
+                None => {
+                    for (idx, link) in links.iter().enumerate() {
+                        eprintln!("[{}] {}", idx + 1, link);
+                    }
+                    &links[prompt_pick("link", links.len())?]
+                }
+            };
+            println!("{}", link);
+            Ok(())
+        }
+        Command::Links { item, depth, pick } => {
+            let mut thread = service
+                .fetch_thread(item, depth, CommentWindow::default())
+                .await?;
+            for comment in &mut thread.comments {
+                comment.hide_dead();
+            }
+            let links = thread.links();
+            if links.is_empty() {
+                bail!("Item {} has no links", item);
+            }
+            if pick {
+                // the list and the prompt go to stderr so that only the picks end up on stdout
+                for (idx, link) in links.iter().enumerate() {
+                    eprintln!("[{}] {}", idx + 1, link);
+                }
+                for idx in prompt_picks("links", links.len())? {
+                    println!("{}", links[idx].url);
+                }
+                return Ok(());
+            }
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&links)?),
+                OutputFormat::JsonLines => links.iter().for_each(print_json_line),
+                OutputFormat::Plain => links.iter().for_each(|link| println!("{}", link.url)),
+                _ => {
+                    for (idx, link) in links.iter().enumerate() {
+                        println!("[{}] {}", idx + 1, Themed(link, theme));
+                    }
+                }
+            }
+            Ok(())
+        }
+        Command::Search { query, page } => {
+            let items = service
+                .search_stories(&query, page.page, page.limit)
+                .await?;
+            let footer = format!(
+                "Enjoy the top {} HN stories matching '{}'!",
+                items.len(),
+                query
+            );
+            print_items(&items, &page, &footer, false, format, theme)
+        }
+        Command::Past { url, limit } => {
+            let items = service.fetch_past_discussions(&url, limit).await?;
+            let footer = format!("{} past HN discussions of {}", items.len(), url);
+            let page = PageArgs {
+                page: 1,
+                limit,
+                launch_only: false,
+                by_domain: false,
+                by_velocity: false,
+            };
+            print_items(&items, &page, &footer, false, format, theme)
+        }
+        Command::Similar { item, limit } => {
+            let items = service.fetch_similar_stories(item, limit).await?;
+            let footer = format!("{} other HN submissions like {}", items.len(), item);
+            let page = PageArgs {
+                page: 1,
+                limit,
+                launch_only: false,
+                by_domain: false,
+                by_velocity: false,
+            };
+            print_items(&items, &page, &footer, false, format, theme)
+        }
+        Command::User {
+            user,
+            page,
+            favorites: true,
+        } => {
+            let items = service
+                .fetch_user_favorites(&user, page.page, page.limit)
+                .await?;
+            let footer = format!("{} HN favorites of {}", items.len(), user);
+            print_items(&items, &page, &footer, false, format, theme)
+        }
+        Command::User { user, page, .. } => {
+            let items = service
+                .fetch_user_submissions(&user, page.page, page.limit)
+                .await?;
+            let footer = format!("The latest {} HN submissions of {}", items.len(), user);
+            print_items(&items, &page, &footer, false, format, theme)
+        }
+        Command::Hiring(hiring) => run_hiring(&hiring, format, service).await,
+        Command::Pick(pick) => run_pick(&pick, service).await,
+        Command::Watch(watch) => run_watch(&watch, paths, format, theme, service).await,
+        Command::Topics(topics) => run_topics(&topics, format, theme, service).await,
+        Command::Bookmark { item } => {
+            let mut bookmarks = load_bookmarks(paths)?;
+            let bookmark = service.fetch_bookmark(item).await?;
+            eprintln!("Bookmarked {}", bookmark.url);
+            bookmarks.add(bookmark);
+            bookmarks.save()
+        }
+        Command::Bookmarks { remove } => {
+            let mut bookmarks = load_bookmarks(paths)?;
+            if let Some(item) = remove {
+                if !bookmarks.remove(item) {
+                    bail!("Item {} is not bookmarked", item);
+                }
+                return bookmarks.save();
+            }
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(bookmarks.list())?)
+                }
+                OutputFormat::JsonLines => bookmarks.list().iter().for_each(print_json_line),
+                OutputFormat::Text => {
+                    for bookmark in bookmarks.list() {
+                        println!("{}\n", Themed(bookmark, theme));
+                    }
+                }
+                _ => bookmarks.list().iter().for_each(|b| println!("{}\n", b)),
+            }
+            Ok(())
+        }
+        Command::Activity { limit, clear } => {
+            let mut activity = load_activity(paths)?;
+            if clear {
+                activity.clear();
+                return activity.save();
+            }
+            let events = activity.events();
+            let events = &events[events.len().saturating_sub(limit)..];
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(events)?),
+                OutputFormat::JsonLines => events.iter().for_each(print_json_line),
+                _ => events.iter().for_each(|event| println!("{}", event)),
+            }
+            Ok(())
+        }
+        Command::Paths => {
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(paths)?),
+                OutputFormat::JsonLines => print_json_line(paths),
+                _ => {
+                    println!("config file: {}", paths.config_file.display());
+                    println!("config: {}", paths.config_dir.display());
+                    println!("cache: {}", paths.cache_dir.display());
+                    println!("data: {}", paths.data_dir.display());
+                    println!("logs: {}", paths.log_dir.display());
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Applies the configuration to the arguments and picks the theme to use
+fn load_settings(args: &mut Cli, matches: &ArgMatches) -> Result<(Paths, Config, Theme)> {
+    let paths = Paths::resolve(
+        args.config.clone(),
+        args.config_dir.clone(),
+        args.cache_dir.clone(),
+    )?;
+    paths.create_dirs()?;
+    // unlike the default location, a file given explicitly is expected to exist
+    if args.config.is_some() && !paths.config_file.is_file() {
+        anyhow::bail!("Could not find `{}`", paths.config_file.display());
+    }
+    let mut config = Config::load(&paths.config_file)?;
+    config.apply_env(|var| std::env::var(var).ok())?;
+    args.apply_config(matches, &config);
+    let theme = match &args.theme {
+        Some(name) => Theme::builtin(name).with_context(|| format!("Unknown theme {}", name))?,
+        None => Theme::load(&paths.theme_file())?,
+    };
+    // https://no-color.org: only a non-empty value counts
+    let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    match args.no_color || no_color_env {
+        true => Ok((paths, config, theme.monochrome())),
+        false => Ok((paths, config, theme)),
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let mut args = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let (paths, config, theme) = match load_settings(&mut args, &matches) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(exitcode::CONFIG);
+        }
+    };
 
-    let hn_cli_service = HackerNewsCliServiceImpl::new(None);
+    let hn_client = config
+        .api_url
+        .as_deref()
+        .map(HackerNewsClientImpl::with_api_url);
+    let hn_cli_service = HackerNewsCliServiceImpl::new(hn_client);
 
     if let Err(e) = validate_args(&args, HackerNewsCliServiceImpl::get_valid_story_types()) {
         eprintln!("Error: {}", e);
         std::process::exit(exitcode::USAGE);
     }
 
-    match run(args, &hn_cli_service).await {
+    match run(args, &paths, &theme, &hn_cli_service).await {
         Ok(_) => std::process::exit(exitcode::OK),
         Err(e) => {
             eprintln!("Error: {}", e);
@@ -67,13 +1494,353 @@ async fn main() -> Result<()> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_item_id() {
+        assert_eq!(parse_item_id("12345").unwrap(), 12345);
+        assert_eq!(
+            parse_item_id("https://news.ycombinator.com/item?id=12345").unwrap(),
+            12345
+        );
+        assert_eq!(
+            parse_item_id("https://news.ycombinator.com/item?id=12345&p=2").unwrap(),
+            12345
+        );
+        assert!(parse_item_id("https://rust-lang.org").is_err());
+    }
+
+    #[test]
+    fn test_parse_subcommands() {
+        let args = Cli::try_parse_from(["hn", "top", "--page", "2", "--limit", "30"]).unwrap();
+        match args.command {
+            Some(Command::Top(page)) => {
+                assert_eq!(page.page, 2);
+                assert_eq!(page.limit, 30);
+            }
+            command => panic!("Unexpected command {:?}", command),
+        }
+
+        let args = Cli::try_parse_from(["hn", "comments", "12345"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Comments {
+                item: 12345,
+                thread: ThreadArgs {
+                    depth: 3,
+                    max_lines: 60,
+                    previous: false,
+                    show_dead: false,
+                    no_show_dead: false,
+                    hide_deleted: false,
+                    no_hide_deleted: false,
+                    flair: false,
+                    expand_all: false,
+                    ..
+                }
+            })
+        ));
+        let args = Cli::try_parse_from(["hn", "12345", "--previous"]).unwrap();
+        assert!(args.thread.previous);
+        assert_eq!(args.thread.sort, CommentOrder::Hn);
+        let args = Cli::try_parse_from(["hn", "12345", "--sort", "replies"]).unwrap();
+        assert_eq!(args.thread.sort, CommentOrder::Replies);
+        assert!(Cli::try_parse_from(["hn", "12345", "--expand-all", "--depth", "2"]).is_err());
+        let args =
+            Cli::try_parse_from(["hn", "12345", "--per-level", "50", "--skip", "100"]).unwrap();
+        assert_eq!(
+            args.thread.window(),
+            CommentWindow {
+                skip: 100,
+                limit: Some(50),
+                after: None,
+            }
+        );
+
+        let args =
+            Cli::try_parse_from(["hn", "raw", "https://news.ycombinator.com/item?id=42"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Raw { item: 42 })));
+
+        let args = Cli::try_parse_from(["hn", "permalink", "42"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Permalink { item: 42 })
+        ));
+
+        let args = Cli::try_parse_from(["hn", "link", "42", "2"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Link {
+                item: 42,
+                number: Some(2)
+            })
+        ));
+        let args = Cli::try_parse_from(["hn", "link", "42"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Link {
+                item: 42,
+                number: None
+            })
+        ));
+        assert!(Cli::try_parse_from(["hn", "link", "42", "0"]).is_err());
+
+        let args = Cli::try_parse_from(["hn", "topics", "rust", "--pages", "3"]).unwrap();
+        let Some(Command::Topics(topics)) = args.command else {
+            panic!("expected the topics command");
+        };
+        assert_eq!(topics.term.as_deref(), Some("rust"));
+        assert_eq!(topics.pages, 3);
+        assert_eq!(topics.story_type, "top");
+
+        assert!(Cli::try_parse_from(["hn", "--max-lines", "10"]).is_err());
+        assert!(Cli::try_parse_from(["hn", "12345", "--max-lines", "10"]).is_ok());
+
+        let args = Cli::try_parse_from(["hn", "--type", "new", "--page", "3", "--page-size", "30"])
+            .unwrap();
+        assert_eq!(args.story_type, "new");
+        assert_eq!(args.page, 3);
+        assert_eq!(args.length, 30);
+
+        let args = Cli::try_parse_from(["hn", "12345"]).unwrap();
+        assert!(args.command.is_none());
+        assert_eq!(args.item, Some(12345));
+
+        assert!(Cli::try_parse_from(["hn", "top", "--page", "0"]).is_err());
+
+        let args = Cli::try_parse_from(["hn", "comments", "12345", "--json"]).unwrap();
+        assert_eq!(args.output_format(), OutputFormat::Json);
+
+        let args = Cli::try_parse_from(["hn", "new", "--jsonl"]).unwrap();
+        assert_eq!(args.output_format(), OutputFormat::JsonLines);
+        assert!(Cli::try_parse_from(["hn", "new", "--json", "--jsonl"]).is_err());
+        assert!(Cli::try_parse_from(["hn", "new", "--jsonl", "--by-velocity"]).is_err());
+        assert!(Cli::try_parse_from(["hn", "top", "--jsonl", "--by-domain"]).is_err());
+        let streamed = |flags: &[&str]| {
+            let args = [["hn", "comments", "12345", "--jsonl"].as_slice(), flags].concat();
+            Cli::try_parse_from(args)
+        };
+        assert!(streamed(&["--per-level", "50", "--skip", "100"]).is_ok());
+        assert!(streamed(&["--expand-all"]).is_ok());
+        assert!(streamed(&["--resume"]).is_err());
+        assert!(streamed(&["--sort", "newest"]).is_err());
+        assert!(streamed(&["--hide-deleted"]).is_err());
+        assert!(streamed(&["--author", "me"]).is_err());
+        assert!(streamed(&["--grep", "rust"]).is_err());
+        assert!(streamed(&["--max-lines", "10"]).is_err());
+        assert!(Cli::try_parse_from(["hn", "12345", "--json", "--max-lines", "0"]).is_err());
+        assert!(Cli::try_parse_from(["hn", "12345", "--json"]).is_ok());
+        assert!(streamed(&["--speak"]).is_err());
+        assert!(Cli::try_parse_from(["hn", "12345", "--json", "--speak"]).is_err());
+        assert!(streamed(&["--previous"]).is_err());
+        assert!(streamed(&["--flair"]).is_err());
+        assert!(streamed(&["--flat"]).is_err());
+
+        let args = Cli::try_parse_from(["hn", "--plain"]).unwrap();
+        assert_eq!(args.output_format(), OutputFormat::Plain);
+    }
+
+    #[test]
+    fn test_validate_pick_args() {
+        let valid_story_types = HackerNewsCliServiceImpl::get_valid_story_types();
+        let args = Cli::try_parse_from(["hn", "pick", "-s", "show", "--id"]).unwrap();
+        assert!(validate_args(&args, valid_story_types.clone()).is_ok());
+        let args = Cli::try_parse_from(["hn", "pick", "-s", "invalid"]).unwrap();
+        assert!(validate_args(&args, valid_story_types).is_err());
+    }
+
+    #[test]
+    fn test_listed_items() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let item = |id, url: &str, score, age| HNCLIItem {
+            id,
+            title: "Story".to_string(),
+            url: url.to_string(),
+            author: "me".to_string(),
+            time: "2020-05-07 22:01:28".to_string(),
+            time_ago: "0 seconds ago".to_string(),
+            timestamp: now - age,
+            score: Some(score),
+            comments: None,
+            is_job: false,
+            is_launch: id == 3,
+            poll_options: Vec::new(),
+        };
+        let items = vec![
+            item(1, "https://a.com", 100, 10 * 3600),
+            item(2, "https://b.com", 50, 3600),
+            item(3, "https://a.com/launch", 30, 3600),
+        ];
+        let mut page = PageArgs {
+            page: 1,
+            limit: 10,
+            launch_only: false,
+            by_domain: false,
+            by_velocity: false,
+        };
+        let ids = |page: &PageArgs| -> Vec<i64> {
+            let listed = listed_items(&items, page).unwrap();
+            listed.iter().map(|item| item.id).collect()
+        };
+        assert_eq!(ids(&page), vec![1, 2, 3]);
+        page.by_velocity = true;
+        assert_eq!(ids(&page), vec![2, 3, 1]);
+        page.launch_only = true;
+        assert_eq!(ids(&page), vec![3]);
+
+        let listed: Vec<_> = items.iter().collect();
+        let groups: Vec<(&str, Vec<i64>)> = pick_groups(&listed, true)
+            .into_iter()
+            .map(|(domain, group)| (domain, group.iter().map(|item| item.id).collect()))
+            .collect();
+        assert_eq!(groups, vec![("a.com", vec![1, 3]), ("b.com", vec![2])]);
+        assert_eq!(pick_groups(&listed, false).len(), 1);
+    }
+
+    #[test]
+    fn test_parse_picks() {
+        assert_eq!(parse_picks("2, 3 1\n", 3), Some(vec![1, 2, 0]));
+        assert_eq!(parse_picks("all\n", 2), Some(vec![0, 1]));
+        assert_eq!(parse_picks("4", 3), None);
+        assert_eq!(parse_picks("\n", 3), None);
+        assert_eq!(parse_fzf_pick("2\tRust 2.0 [1 points]\n", 3), Some(2));
+        assert_eq!(parse_fzf_pick("3\tRust 2.0\n", 3), None);
+        assert_eq!(parse_fzf_pick("Rust 2.0\n", 3), None);
+    }
+
+    #[test]
+    fn test_parse_watch_command() {
+        assert_eq!(
+            parse_watch_command("refresh\n").unwrap(),
+            WatchCommand::Refresh
+        );
+        assert_eq!(
+            parse_watch_command(" type  top ").unwrap(),
+            WatchCommand::Type("top".to_string())
+        );
+        assert!(parse_watch_command("type invalid").is_err());
+        assert!(parse_watch_command("json please").is_err());
+    }
+
+    #[test]
+    fn test_parse_watch() {
+        let args =
+            Cli::try_parse_from(["hn", "watch", "--type", "top", "--interval", "120"]).unwrap();
+        match args.command {
+            Some(Command::Watch(watch)) => {
+                assert_eq!(watch.story_type, "top");
+                assert_eq!(watch.interval, 120);
+                assert_eq!(watch.limit, 30);
+            }
+            command => panic!("Unexpected command {:?}", command),
+        }
+        assert!(Cli::try_parse_from(["hn", "watch", "--interval", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_apply_config() {
+        let config = Config {
+            story_type: Some("top".to_string()),
+            page_size: Some(30),
+            comment_depth: Some(5),
+            theme: Some("dracula".to_string()),
+            api_url: None,
+            show_dead: Some(true),
+            hide_deleted: Some(true),
+            tts_command: None,
+            collapse: CollapseRules {
+                low_effort: true,
+                ..CollapseRules::default()
+            },
+        };
+        let parse = |argv: &[&str]| {
+            let matches = Cli::command().try_get_matches_from(argv).unwrap();
+            let mut args = Cli::from_arg_matches(&matches).unwrap();
+            args.apply_config(&matches, &config);
+            args
+        };
+
+        let args = parse(&["hn"]);
+        assert_eq!(args.story_type, "top");
+        assert_eq!(args.length, 30);
+        assert_eq!(args.theme.as_deref(), Some("dracula"));
+
+        let args = parse(&["hn", "-s", "new", "-l", "5", "--theme", "gruvbox"]);
+        assert_eq!(args.story_type, "new");
+        assert_eq!(args.length, 5);
+        assert_eq!(args.theme.as_deref(), Some("gruvbox"));
+
+        match parse(&["hn", "best", "--page", "2"]).command {
+            Some(Command::Best(page)) => assert_eq!(page.limit, 30),
+            command => panic!("Unexpected command {:?}", command),
+        }
+        match parse(&["hn", "comments", "12345"]).command {
+            Some(Command::Comments { thread, .. }) => {
+                assert_eq!(thread.depth, 5);
+                assert!(thread.show_dead);
+                assert!(thread.hide_deleted);
+                assert!(thread.collapse.low_effort);
+            }
+            command => panic!("Unexpected command {:?}", command),
+        }
+        assert!(!parse(&["hn", "12345", "--no-show-dead"]).thread.show_dead);
+        assert!(
+            !parse(&["hn", "12345", "--no-hide-deleted"])
+                .thread
+                .hide_deleted
+        );
+        assert!(
+            parse(&["hn", "12345", "--no-show-dead", "--show-dead"])
+                .thread
+                .show_dead
+        );
+    }
+
     #[test]
     fn test_validate_args() {
         let valid_story_types = HackerNewsCliServiceImpl::get_valid_story_types();
-        for story_type in ["best", "new", "top", "not_ok", "invalid", "etc"].into_iter() {
+        for story_type in [
+            "ask", "best", "job", "new", "show", "top", "not_ok", "invalid", "etc",
+        ]
+        .into_iter()
+        {
             let args = Cli {
+                command: None,
+                item: None,
+                thread: ThreadArgs {
+                    depth: 3,
+                    max_lines: 60,
+                    previous: false,
+                    show_dead: false,
+                    no_show_dead: false,
+                    hide_deleted: false,
+                    no_hide_deleted: false,
+                    flair: false,
+                    expand_all: false,
+                    per_level: None,
+                    skip: 0,
+                    resume: false,
+                    grep: None,
+                    author: None,
+                    sort: CommentOrder::Hn,
+                    flat: false,
+                    speak: false,
+                    collapse: CollapseRules::default(),
+                    tts_command: None,
+                },
                 story_type: story_type.to_string(),
+                page: 1,
                 length: 35, // length is validated by clap
+                json: false,
+                jsonl: false,
+                plain: false,
+                csv: false,
+                config: None,
+                config_dir: None,
+                cache_dir: None,
+                theme: None,
+                no_color: false,
             };
             let result = validate_args(&args, valid_story_types.clone());
             if valid_story_types.contains(story_type) {
@@ -82,5 +1849,9 @@ mod tests {
                 assert!(result.is_err());
             }
         }
+        let args = Cli::try_parse_from(["hn", "topics", "--type", "new"]).unwrap();
+        assert!(validate_args(&args, valid_story_types.clone()).is_ok());
+        let args = Cli::try_parse_from(["hn", "topics", "--type", "bogus"]).unwrap();
+        assert!(validate_args(&args, valid_story_types).is_err());
     }
 }
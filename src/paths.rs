@@ -0,0 +1,104 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+const APP_DIR: &str = "hn-cli";
+const CONFIG_FILE: &str = "config.toml";
+
+/// Where the CLI keeps its files, following the XDG base directory specification
+/// or the known folders on Windows
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Paths {
+    pub config_file: PathBuf,
+    pub config_dir: PathBuf,
+    pub cache_dir: PathBuf,
+    pub data_dir: PathBuf,
+    pub log_dir: PathBuf,
+}
+
+impl Paths {
+    /// Resolves the directories from the environment, the overrides taking precedence
+    pub fn resolve(
+        config_file: Option<PathBuf>,
+        config_dir: Option<PathBuf>,
+        cache_dir: Option<PathBuf>,
+    ) -> Result<Self> {
+        let mut paths = Self::from_env()?;
+        if let Some(config_dir) = config_dir {
+            paths.config_file = config_dir.join(CONFIG_FILE);
+            paths.config_dir = config_dir;
+        }
+        if let Some(config_file) = config_file {
+            paths.config_file = config_file;
+        }
+        if let Some(cache_dir) = cache_dir {
+            paths.cache_dir = cache_dir;
+        }
+        Ok(paths)
+    }
+
+    #[cfg(not(windows))]
+    fn from_env() -> Result<Self> {
+        let home = std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .context("Could not find the home directory, HOME is not set")?;
+        let xdg_dir = |var: &str, default: &str| {
+            xdg_app_dir(std::env::var_os(var).map(PathBuf::from), &home, default)
+        };
+        let config_dir = xdg_dir("XDG_CONFIG_HOME", ".config");
+        Ok(Self {
+            config_file: config_dir.join(CONFIG_FILE),
+            config_dir,
+            cache_dir: xdg_dir("XDG_CACHE_HOME", ".cache"),
+            data_dir: xdg_dir("XDG_DATA_HOME", ".local/share"),
+            log_dir: xdg_dir("XDG_STATE_HOME", ".local/state"),
+        })
+    }
+
+    /// The configuration roams with the user profile, the rest stays on the machine
+    #[cfg(windows)]
+    fn from_env() -> Result<Self> {
+        let known_folder = |var: &str| {
+            std::env::var_os(var)
+                .map(|path| PathBuf::from(path).join(APP_DIR))
+                .with_context(|| format!("Could not find the {} folder", var))
+        };
+        let config_dir = known_folder("APPDATA")?;
+        let local_dir = known_folder("LOCALAPPDATA")?;
+        Ok(Self {
+            config_file: config_dir.join(CONFIG_FILE),
+            config_dir,
+            cache_dir: local_dir.join("cache"),
+            data_dir: local_dir.join("data"),
+            log_dir: local_dir.join("logs"),
+        })
+    }
+
+    pub fn theme_file(&self) -> PathBuf {
+        self.config_dir.join("theme.toml")
+    }
+
+    /// Creates the directories that do not exist yet, e.g. on the first run
+    pub fn create_dirs(&self) -> Result<()> {
+        for dir in [
+            &self.config_dir,
+            &self.cache_dir,
+            &self.data_dir,
+            &self.log_dir,
+        ] {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Could not create `{}`", dir.display()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Relative XDG paths are invalid and must be ignored according to the specification
+#[cfg_attr(windows, allow(dead_code))]
+pub fn xdg_app_dir(xdg_home: Option<PathBuf>, home: &Path, default: &str) -> PathBuf {
+    let base = match xdg_home {
+        Some(path) if path.is_absolute() => path,
+        _ => home.join(default),
+    };
+    base.join(APP_DIR)
+}
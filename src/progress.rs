@@ -0,0 +1,104 @@
+use crate::fs_utils::write_atomic;
+use crate::schema::{load_versioned, Migration};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Far more threads than anyone comes back to, the oldest ones being forgotten first
+const MAX_THREADS: usize = 1000;
+
+const MIGRATIONS: &[Migration] = &[];
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Progress {
+    story: i64,
+    /// The last top level comment shown by `--resume`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comment: Option<i64>,
+    /// When the thread was last shown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    visited: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProgressFile {
+    version: u64,
+    #[serde(default)]
+    threads: Vec<Progress>,
+}
+
+/// How far each thread read in chunks was read and when each thread was last shown, kept
+/// between runs
+pub struct ReadingProgress {
+    path: PathBuf,
+    /// The most recently read last
+    threads: Vec<Progress>,
+    corrupt_backup: Option<PathBuf>,
+}
+
+impl ReadingProgress {
+    /// Starts afresh when the file is missing or corrupted, the latter being moved aside
+    pub fn load(path: &Path) -> Result<Self> {
+        let loaded = load_versioned::<ProgressFile>(path, MIGRATIONS)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            threads: loaded.value.map(|file| file.threads).unwrap_or_default(),
+            corrupt_backup: loaded.corrupt_backup,
+        })
+    }
+
+    /// Where the file was moved if it was corrupted when loaded
+    pub fn corrupt_backup(&self) -> Option<&Path> {
+        self.corrupt_backup.as_deref()
+    }
+
+    /// The last top level comment shown of a story, if it was read before
+    pub fn last_read(&self, story: i64) -> Option<i64> {
+        self.find(story).and_then(|progress| progress.comment)
+    }
+
+    pub fn mark_read(&mut self, story: i64, comment: i64) {
+        self.touch(story).comment = Some(comment);
+    }
+
+    /// When the thread of a story was last shown, if ever
+    pub fn last_visit(&self, story: i64) -> Option<u64> {
+        self.find(story).and_then(|progress| progress.visited)
+    }
+
+    pub fn mark_visited(&mut self, story: i64, timestamp: u64) {
+        self.touch(story).visited = Some(timestamp);
+    }
+
+    fn find(&self, story: i64) -> Option<&Progress> {
+        self.threads.iter().find(|progress| progress.story == story)
+    }
+
+    /// The progress of a story, moved last as the most recently read
+    fn touch(&mut self, story: i64) -> &mut Progress {
+        let idx = self
+            .threads
+            .iter()
+            .position(|progress| progress.story == story);
+        let progress = match idx {
+            Some(idx) => self.threads.remove(idx),
+            None => Progress {
+                story,
+                comment: None,
+                visited: None,
+            },
+        };
+        self.threads.push(progress);
+        let overflow = self.threads.len().saturating_sub(MAX_THREADS);
+        self.threads.drain(..overflow);
+        self.threads.last_mut().expect("just pushed")
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let file = ProgressFile {
+            version: MIGRATIONS.len() as u64,
+            threads: self.threads.clone(),
+        };
+        write_atomic(&self.path, serde_json::to_string(&file)?.as_bytes())
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Stays well below what the HN API tolerates, even while fetching deep comment trees
+pub const MAX_REQUESTS_PER_MINUTE: usize = 600;
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Caps the number of requests sent over any sliding minute, delaying the extra ones
+#[derive(Debug)]
+pub struct RateLimiter {
+    max_per_minute: usize,
+    sent: Mutex<VecDeque<Instant>>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(MAX_REQUESTS_PER_MINUTE)
+    }
+}
+
+impl RateLimiter {
+    pub fn new(max_per_minute: usize) -> Self {
+        Self {
+            max_per_minute: max_per_minute.max(1),
+            sent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Waits until a request can be sent without exceeding the cap, then records it
+    pub async fn acquire(&self) {
+        // holding the lock while sleeping keeps the waiting requests in order
+        let mut sent = self.sent.lock().await;
+        let now = Instant::now();
+        if let Some(wait) = wait_time(&mut sent, now, self.max_per_minute) {
+            tokio::time::sleep(wait).await;
+        }
+        sent.push_back(Instant::now());
+    }
+}
+
+/// Forgets the requests older than the window and tells how long to wait for a free slot
+pub fn wait_time(
+    sent: &mut VecDeque<Instant>,
+    now: Instant,
+    max_per_minute: usize,
+) -> Option<Duration> {
+    while sent
+        .front()
+        .is_some_and(|oldest| now.duration_since(*oldest) >= WINDOW)
+    {
+        sent.pop_front();
+    }
+    if sent.len() < max_per_minute {
+        return None;
+    }
+    let oldest = sent.pop_front()?;
+    Some(WINDOW - now.duration_since(oldest))
+}
@@ -0,0 +1,74 @@
+use crate::fs_utils::move_aside;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Upgrades a persisted value from one schema version to the next
+pub type Migration = fn(Value) -> Value;
+
+/// Brings a persisted value to the latest schema version, `migrations[n]` upgrading version `n`
+/// to `n + 1`. Values written before versions were stamped count as version 0
+pub fn migrate(mut value: Value, migrations: &[Migration]) -> Result<Value> {
+    let latest = migrations.len() as u64;
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version > latest {
+        bail!(
+            "Written by a newer release of hn (schema version {}, this release supports up to {})",
+            version,
+            latest
+        );
+    }
+    for (from, migration) in migrations.iter().enumerate().skip(version as usize) {
+        value = migration(value);
+        if let Some(object) = value.as_object_mut() {
+            object.insert("version".to_string(), Value::from(from as u64 + 1));
+        }
+    }
+    Ok(value)
+}
+
+/// A persisted file as read by `load_versioned`
+pub struct Loaded<T> {
+    /// The content at the latest schema version, `None` when the file is missing or corrupted
+    pub value: Option<T>,
+    /// Where the file was moved if it was corrupted
+    pub corrupt_backup: Option<PathBuf>,
+}
+
+/// Reads a versioned JSON file and brings it to the latest schema version. A file that cannot
+/// be parsed is moved aside for the caller to start afresh without losing it, while a file
+/// written by a newer release is an error, as saving over it would drop what it holds
+pub fn load_versioned<T: DeserializeOwned>(
+    path: &Path,
+    migrations: &[Migration],
+) -> Result<Loaded<T>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(Loaded {
+                value: None,
+                corrupt_backup: None,
+            })
+        }
+        Err(e) => return Err(e).with_context(|| format!("Could not read `{}`", path.display())),
+    };
+    let value = match serde_json::from_str::<Value>(&content) {
+        // the reason is kept in the message, only the outermost one being printed
+        Ok(value) => Some(
+            migrate(value, migrations)
+                .map_err(|e| anyhow!("Could not load `{}`: {}", path.display(), e))?,
+        ),
+        Err(_) => None,
+    };
+    match value.and_then(|value| serde_json::from_value::<T>(value).ok()) {
+        Some(value) => Ok(Loaded {
+            value: Some(value),
+            corrupt_backup: None,
+        }),
+        None => Ok(Loaded {
+            value: None,
+            corrupt_backup: Some(move_aside(path)?),
+        }),
+    }
+}
@@ -0,0 +1,90 @@
+use crate::rate_limit::RateLimiter;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use mockall::automock;
+use reqwest::header::USER_AGENT;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+
+const ALGOLIA_API_URL: &str = "https://hn.algolia.com/api/v1/";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    #[serde(rename = "objectID")]
+    pub object_id: String,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub author: String,
+    pub points: Option<i32>,
+    pub num_comments: Option<i32>,
+    pub created_at_i: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    hits: Vec<SearchHit>,
+}
+
+#[automock]
+#[async_trait]
+pub trait SearchClient {
+    /// Searches stories by relevance, pages start at 1
+    async fn search_stories(&self, query: &str, page: u8, n: u8) -> Result<Vec<SearchHit>>;
+    async fn search_stories_by_url(&self, story_url: &str, n: u8) -> Result<Vec<SearchHit>>;
+}
+
+#[derive(Default)]
+pub struct SearchClientImpl {
+    client: Client,
+    limiter: RateLimiter,
+}
+
+#[async_trait]
+impl SearchClient for SearchClientImpl {
+    async fn search_stories(&self, query: &str, page: u8, n: u8) -> Result<Vec<SearchHit>> {
+        self.search(&[
+            ("query", query),
+            ("tags", "story"),
+            ("page", &page.saturating_sub(1).to_string()),
+            ("hitsPerPage", &n.to_string()),
+        ])
+        .await
+    }
+
+    async fn search_stories_by_url(&self, story_url: &str, n: u8) -> Result<Vec<SearchHit>> {
+        self.search(&[
+            ("query", story_url),
+            ("restrictSearchableAttributes", "url"),
+            ("tags", "story"),
+            ("hitsPerPage", &n.to_string()),
+        ])
+        .await
+    }
+}
+
+impl SearchClientImpl {
+    pub fn new() -> Self {
+        Self {
+            client: Client::new(),
+            limiter: RateLimiter::default(),
+        }
+    }
+
+    async fn search(&self, params: &[(&str, &str)]) -> Result<Vec<SearchHit>> {
+        let url = format!("{}search", ALGOLIA_API_URL);
+        self.limiter.acquire().await;
+        let resp = self
+            .client
+            .get(&url)
+            .query(params)
+            .header(USER_AGENT, "reqwest")
+            .send()
+            .await
+            .with_context(|| format!("Could not retrieve data from `{}`", url))?;
+        if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+            bail!("Rate limited by `{}`, try again later", url);
+        }
+        let resp = resp.json::<SearchResponse>().await?;
+        Ok(resp.hits)
+    }
+}
@@ -0,0 +1,64 @@
+use crate::fs_utils::write_atomic;
+use crate::schema::{load_versioned, Migration};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+/// Enough to cover several days of the new feed without letting the file grow forever
+const MAX_SEEN_IDS: usize = 5000;
+
+/// The first release stored a bare array of ids
+const MIGRATIONS: &[Migration] = &[|ids| json!({ "ids": ids })];
+
+#[derive(Serialize, Deserialize)]
+struct SeenIdsFile {
+    version: u64,
+    ids: Vec<i64>,
+}
+
+/// Ids of the items already shown by `watch`, persisted between polls and runs
+#[derive(Debug)]
+pub struct SeenIds {
+    path: PathBuf,
+    ids: Vec<i64>,
+    corrupt_backup: Option<PathBuf>,
+}
+
+impl SeenIds {
+    /// Starts afresh when the file is missing or corrupted, the latter being moved aside
+    pub fn load(path: &Path) -> Result<Self> {
+        let loaded = load_versioned::<SeenIdsFile>(path, MIGRATIONS)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            ids: loaded.value.map(|file| file.ids).unwrap_or_default(),
+            corrupt_backup: loaded.corrupt_backup,
+        })
+    }
+
+    /// Where the file was moved if it was corrupted when loaded
+    pub fn corrupt_backup(&self) -> Option<&Path> {
+        self.corrupt_backup.as_deref()
+    }
+
+    /// Records the ids and returns the ones that were not seen before, in the same order
+    pub fn mark_seen(&mut self, ids: &[i64]) -> Vec<i64> {
+        let unseen: Vec<i64> = ids
+            .iter()
+            .filter(|id| !self.ids.contains(id))
+            .copied()
+            .collect();
+        self.ids.extend(&unseen);
+        let overflow = self.ids.len().saturating_sub(MAX_SEEN_IDS);
+        self.ids.drain(..overflow);
+        unseen
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let file = SeenIdsFile {
+            version: MIGRATIONS.len() as u64,
+            ids: self.ids.clone(),
+        };
+        write_atomic(&self.path, serde_json::to_string(&file)?.as_bytes())
+    }
+}
@@ -0,0 +1,190 @@
+use anyhow::{Context, Result};
+use console::{Style, StyledObject};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// The themes bundled with the CLI, selectable by name
+pub const BUILTIN_THEMES: &[&str] = &["default", "gruvbox", "solarized-dark", "dracula"];
+
+/// What is kept of the styles in monochrome mode
+const MODIFIERS: &[&str] = &[
+    "bold",
+    "dim",
+    "italic",
+    "underlined",
+    "reverse",
+    "strikethrough",
+];
+
+/// The styles of the text output, each one written like "yellow.bold" or "208.underlined"
+/// and left empty to keep the default terminal color
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub title: String,
+    pub author: String,
+    pub details: String,
+    pub url: String,
+    pub comment_author: String,
+    pub muted: String,
+    /// Italic text of the comments
+    pub emphasis: String,
+    /// Inline code and code blocks of the comments
+    pub code: String,
+    /// The "OP" badge of the comments written by the story author
+    pub op: String,
+    /// The text matching the pattern searched with `--grep`
+    pub highlight: String,
+    /// The styles the comment authors are spread over by name, `comment_author` being used
+    /// for all of them when empty
+    pub author_palette: Vec<String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            title: "bold".to_string(),
+            author: "cyan".to_string(),
+            details: "yellow".to_string(),
+            url: "blue".to_string(),
+            comment_author: "cyan.bold".to_string(),
+            muted: "dim".to_string(),
+            emphasis: "italic".to_string(),
+            code: "green".to_string(),
+            op: "magenta.bold".to_string(),
+            highlight: "reverse".to_string(),
+            author_palette: ["cyan", "green", "yellow", "blue", "magenta", "red"]
+                .map(|color| format!("{}.bold", color))
+                .to_vec(),
+        }
+    }
+}
+
+impl Theme {
+    /// No style at all, what the `Display` implementations use
+    pub fn plain() -> Self {
+        Self {
+            title: String::new(),
+            author: String::new(),
+            details: String::new(),
+            url: String::new(),
+            comment_author: String::new(),
+            muted: String::new(),
+            emphasis: String::new(),
+            code: String::new(),
+            op: String::new(),
+            highlight: String::new(),
+            author_palette: Vec::new(),
+        }
+    }
+
+    /// The bundled themes use the 256-color palette to stay close to the original colors
+    pub fn builtin(name: &str) -> Option<Self> {
+        let (styles, palette) = match name {
+            "default" => return Some(Self::default()),
+            "gruvbox" => (
+                [
+                    "214.bold", "108", "175", "109", "142.bold", "245", "italic", "208",
+                    "167.bold", "reverse",
+                ],
+                ["167", "142", "214", "109", "175", "108"],
+            ),
+            "solarized-dark" => (
+                [
+                    "136.bold", "37", "61", "33", "64.bold", "241", "italic", "166", "125.bold",
+                    "reverse",
+                ],
+                ["160", "64", "136", "33", "125", "37"],
+            ),
+            "dracula" => (
+                [
+                    "212.bold", "117", "228", "141", "84.bold", "61", "italic", "84", "203.bold",
+                    "reverse",
+                ],
+                ["203", "84", "228", "117", "212", "141"],
+            ),
+            _ => return None,
+        };
+        let [title, author, details, url, comment_author, muted, emphasis, code, op, highlight] =
+            styles.map(String::from);
+        Some(Self {
+            title,
+            author,
+            details,
+            url,
+            comment_author,
+            muted,
+            emphasis,
+            code,
+            op,
+            highlight,
+            author_palette: palette.map(|color| format!("{}.bold", color)).to_vec(),
+        })
+    }
+
+    /// The style of a comment author, always the same one for a given name
+    pub fn comment_author_style(&self, author: &str) -> &str {
+        if self.author_palette.is_empty() {
+            return &self.comment_author;
+        }
+        // FNV-1a, stable across runs and Rust versions unlike the std hasher
+        let hash = author.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        });
+        &self.author_palette[(hash % self.author_palette.len() as u64) as usize]
+    }
+
+    /// Drops the colors of every style, only keeping modifiers such as bold or dim
+    pub fn monochrome(&self) -> Self {
+        let strip = |style: &String| {
+            style
+                .split('.')
+                .filter(|part| MODIFIERS.contains(part))
+                .collect::<Vec<_>>()
+                .join(".")
+        };
+        Self {
+            title: strip(&self.title),
+            author: strip(&self.author),
+            details: strip(&self.details),
+            url: strip(&self.url),
+            comment_author: strip(&self.comment_author),
+            muted: strip(&self.muted),
+            emphasis: strip(&self.emphasis),
+            code: strip(&self.code),
+            op: strip(&self.op),
+            highlight: strip(&self.highlight),
+            author_palette: self.author_palette.iter().map(strip).collect(),
+        }
+    }
+
+    /// Reads the theme, the default one being used when the file is missing
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => {
+                Self::parse(&content).with_context(|| format!("Invalid `{}`", path.display()))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Could not read `{}`", path.display())),
+        }
+    }
+
+    /// The styles that are not given keep their default value
+    pub fn parse(content: &str) -> Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+}
+
+/// Applies a style of the theme to a value
+pub fn paint<D>(style: &str, value: D) -> StyledObject<D> {
+    // console does not know about italic in dotted styles
+    let (italic, others): (Vec<_>, Vec<_>) = style.split('.').partition(|part| *part == "italic");
+    let mut style = Style::from_dotted_str(&others.join("."));
+    if !italic.is_empty() {
+        style = style.italic();
+    }
+    style.apply_to(value)
+}
+
+/// Displays a value with the styles of a theme
+pub struct Themed<'a, T>(pub &'a T, pub &'a Theme);
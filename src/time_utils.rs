@@ -6,7 +6,7 @@ pub fn unix_epoch_to_datetime(unix_epoch: u64) -> String {
 }
 
 pub fn time_ago(epoch_time: u64) -> String {
-    let diff = now() - epoch_time;
+    let diff = now().saturating_sub(epoch_time);
     match diff {
         0..=59 => format!("{} seconds ago", diff),
         60..=3599 => format!("{} minutes ago", diff / 60),
@@ -16,6 +16,16 @@ pub fn time_ago(epoch_time: u64) -> String {
     }
 }
 
+pub fn age_bucket(epoch_time: u64) -> String {
+    // a story of the New feed can be ahead of a skewed local clock
+    let diff = now().saturating_sub(epoch_time);
+    match diff {
+        0..=3599 => "less than an hour ago".to_string(),
+        3600..=86399 => format!("{} hours ago", diff / 3600),
+        _ => format!("{} days ago", diff / 86400),
+    }
+}
+
 pub fn now() -> u64 {
     std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -0,0 +1,58 @@
+use serde::Serialize;
+
+/// Words too common in titles to tell anything about them
+const STOP_WORDS: &[&str] = &[
+    "about", "after", "all", "and", "are", "ask", "but", "can", "did", "does", "for", "from",
+    "has", "have", "how", "its", "new", "not", "now", "off", "one", "our", "out", "over", "show",
+    "than", "that", "the", "their", "this", "top", "using", "via", "was", "what", "when", "where",
+    "which", "who", "why", "will", "with", "you", "your",
+];
+
+/// A term shared by several titles
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Topic {
+    pub term: String,
+    /// The number of titles using the term
+    pub count: usize,
+}
+
+impl std::fmt::Display for Topic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} ({})", self.term, self.count)
+    }
+}
+
+/// The meaningful words of a title, lowercased, e.g. "rust" and "c++" but neither "the" nor "2024"
+pub fn topic_terms(title: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    let words = title
+        .to_lowercase()
+        .split(|c: char| !(c.is_alphanumeric() || c == '+' || c == '#'))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    for word in words {
+        let meaningful = word.chars().count() >= 3
+            && !word.chars().all(|c| c.is_ascii_digit())
+            && !STOP_WORDS.contains(&word.as_str());
+        if meaningful && !terms.contains(&word) {
+            terms.push(word);
+        }
+    }
+    terms
+}
+
+/// The `n` terms used by the most titles, those used by a single title left out
+pub fn extract_topics<'a>(titles: impl IntoIterator<Item = &'a str>, n: usize) -> Vec<Topic> {
+    let mut topics: Vec<Topic> = Vec::new();
+    for term in titles.into_iter().flat_map(topic_terms) {
+        match topics.iter_mut().find(|topic| topic.term == term) {
+            Some(topic) => topic.count += 1,
+            None => topics.push(Topic { term, count: 1 }),
+        }
+    }
+    // stable, so that ties keep the order of the list
+    topics.sort_by_key(|topic| std::cmp::Reverse(topic.count));
+    topics.retain(|topic| topic.count > 1);
+    topics.truncate(n);
+    topics
+}